@@ -0,0 +1,135 @@
+// Derive macros for khygl, enabled in khygl with the khygl-derive feature and re-exported as
+// khygl::vertex::Vertex
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parenthesized, parse_macro_input, token, Data, DeriveInput, Error, Fields, LitInt};
+
+// Implements khygl::vertex::Vertex for a #[repr(C)] struct with named fields. Field attributes:
+// #[vertex(location = N)]: attribute location, defaults to the field index
+// #[vertex(normalized)]: read integers as floats mapped to 0..1 (-1..1 for signed types)
+// #[vertex(float)]: read integers as floats without normalization
+// #[vertex(integer)]: read as int/uint, the default for integer fields
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match vertex(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn is_repr_c(input: &DeriveInput) -> Result<bool, Error> {
+    let mut repr_c = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                repr_c = true;
+            }
+            // arguments of align(N) and packed(N)
+            if meta.input.peek(token::Paren) {
+                let content;
+                parenthesized!(content in meta.input);
+                content.parse::<TokenStream2>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr_c)
+}
+
+enum Format {
+    Default,
+    Float,
+    Normalized,
+    Integer,
+}
+
+fn vertex(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    if !is_repr_c(input)? {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Vertex requires #[repr(C)], so that field offsets are stable",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Vertex can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Vertex can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut attribs = Vec::with_capacity(fields.len());
+    for (index, field) in fields.iter().enumerate() {
+        let mut location = index as u32;
+        let mut format = Format::Default;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("vertex"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("location") {
+                    location = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                } else if meta.path.is_ident("normalized") {
+                    format = Format::Normalized;
+                } else if meta.path.is_ident("float") {
+                    format = Format::Float;
+                } else if meta.path.is_ident("integer") {
+                    format = Format::Integer;
+                } else {
+                    return Err(meta.error("expected location, normalized, float or integer"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let name = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+        let attrib_type = quote! { <#ty as ::khygl::vertex::AttribType> };
+        let format = match format {
+            Format::Default => quote! {
+                if #attrib_type::INTEGER {
+                    ::khygl::vertex::AttribFormat::Integer
+                } else {
+                    ::khygl::vertex::AttribFormat::Float
+                }
+            },
+            Format::Float => quote! { ::khygl::vertex::AttribFormat::Float },
+            Format::Normalized => quote! { ::khygl::vertex::AttribFormat::Normalized },
+            Format::Integer => quote! { ::khygl::vertex::AttribFormat::Integer },
+        };
+        attribs.push(quote! {
+            ::khygl::vertex::VertexAttrib {
+                location: #location,
+                components: #attrib_type::COMPONENTS,
+                type_: #attrib_type::GL_TYPE,
+                format: #format,
+                offset: ::core::mem::offset_of!(Self, #name) as u32,
+            }
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::khygl::vertex::Vertex for #ident #ty_generics #where_clause {
+            const ATTRIBS: &'static [::khygl::vertex::VertexAttrib] = &[#(#attribs),*];
+        }
+    })
+}
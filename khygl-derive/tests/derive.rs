@@ -0,0 +1,103 @@
+use khygl::{
+    buffer::Pod,
+    vertex::{AttribFormat, AttribType, Vertex, VertexAttrib},
+};
+
+#[derive(Clone, Copy, Vertex)]
+#[repr(C)]
+struct Point {
+    position: [f32; 2],
+    #[vertex(normalized)]
+    color: [u8; 4],
+    #[vertex(location = 5)]
+    id: u32,
+    #[vertex(float)]
+    weight: i16,
+    #[vertex(integer)]
+    flags: u16,
+}
+
+unsafe impl Pod for Point {}
+
+#[test]
+fn attribs() {
+    assert_eq!(
+        Point::ATTRIBS,
+        &[
+            VertexAttrib {
+                location: 0,
+                components: 2,
+                type_: gl::FLOAT,
+                format: AttribFormat::Float,
+                offset: 0,
+            },
+            VertexAttrib {
+                location: 1,
+                components: 4,
+                type_: gl::UNSIGNED_BYTE,
+                format: AttribFormat::Normalized,
+                offset: 8,
+            },
+            VertexAttrib {
+                location: 5,
+                components: 1,
+                type_: gl::UNSIGNED_INT,
+                format: AttribFormat::Integer,
+                offset: 12,
+            },
+            VertexAttrib {
+                location: 3,
+                components: 1,
+                type_: gl::SHORT,
+                format: AttribFormat::Float,
+                offset: 16,
+            },
+            VertexAttrib {
+                location: 4,
+                components: 1,
+                type_: gl::UNSIGNED_SHORT,
+                format: AttribFormat::Integer,
+                offset: 18,
+            },
+        ][..]
+    );
+}
+
+#[derive(Clone, Copy, Vertex)]
+#[repr(align(16), C)]
+struct Aligned {
+    position: [f32; 3],
+    #[vertex(normalized)]
+    normal: [i8; 4],
+}
+
+unsafe impl Pod for Aligned {}
+
+#[test]
+fn repr_with_arguments() {
+    let offsets = Aligned::ATTRIBS
+        .iter()
+        .map(|attrib| attrib.offset)
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, [0, 12]);
+    assert_eq!(Aligned::ATTRIBS[1].type_, gl::BYTE);
+    assert_eq!(Aligned::ATTRIBS[1].format, AttribFormat::Normalized);
+}
+
+#[derive(Clone, Copy, Vertex)]
+#[repr(C)]
+struct Generic<T: Pod + AttribType> {
+    position: [f32; 2],
+    value: f32,
+    extra: T,
+}
+
+unsafe impl<T: Pod + AttribType> Pod for Generic<T> {}
+
+#[test]
+fn generic() {
+    let attrib = Generic::<u32>::ATTRIBS[2];
+    assert_eq!(attrib.offset, 12);
+    assert_eq!(attrib.format, AttribFormat::Integer);
+    assert_eq!(Generic::<[f32; 4]>::ATTRIBS[2].components, 4);
+}
@@ -0,0 +1,8 @@
+use khygl::vertex::Vertex;
+
+#[derive(Clone, Copy, Vertex)]
+struct Point {
+    position: [f32; 2],
+}
+
+fn main() {}
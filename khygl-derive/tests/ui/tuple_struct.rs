@@ -0,0 +1,7 @@
+use khygl::vertex::Vertex;
+
+#[derive(Clone, Copy, Vertex)]
+#[repr(C)]
+struct Point([f32; 2]);
+
+fn main() {}
@@ -0,0 +1,10 @@
+use khygl::vertex::Vertex;
+
+#[derive(Clone, Copy, Vertex)]
+#[repr(C)]
+struct Point {
+    #[vertex(normalised)]
+    color: [u8; 4],
+}
+
+fn main() {}
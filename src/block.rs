@@ -0,0 +1,352 @@
+use crate::{check_gl, Error};
+use gl::types::*;
+use std::ffi::CString;
+
+// Interface blocks: uniform blocks (UBOs) and shader storage blocks (SSBOs)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+    Uniform,
+    Storage,
+}
+
+impl BlockKind {
+    fn block_interface(self) -> GLenum {
+        match self {
+            BlockKind::Uniform => gl::UNIFORM_BLOCK,
+            BlockKind::Storage => gl::SHADER_STORAGE_BLOCK,
+        }
+    }
+
+    fn variable_interface(self) -> GLenum {
+        match self {
+            BlockKind::Uniform => gl::UNIFORM,
+            BlockKind::Storage => gl::BUFFER_VARIABLE,
+        }
+    }
+}
+
+fn resource_index(program: GLuint, interface: GLenum, name: &str) -> Result<GLuint, Error> {
+    let c_name = CString::new(name)?;
+    let index = unsafe { gl::GetProgramResourceIndex(program, interface, c_name.as_ptr()) };
+    check_gl()?;
+    if index == gl::INVALID_INDEX {
+        Err(format!("Resource {} not found in program", name).into())
+    } else {
+        Ok(index)
+    }
+}
+
+fn resource_property(
+    program: GLuint,
+    interface: GLenum,
+    index: GLuint,
+    property: GLenum,
+) -> Result<GLint, Error> {
+    let mut value = 0;
+    unsafe {
+        gl::GetProgramResourceiv(
+            program,
+            interface,
+            index,
+            1,
+            &property,
+            1,
+            std::ptr::null_mut(),
+            &mut value,
+        );
+    }
+    check_gl()?;
+    Ok(value)
+}
+
+// name is the block name, not the instance name: `Params` in `uniform Params { ... } params;`
+pub fn block_index(program: GLuint, kind: BlockKind, name: &str) -> Result<GLuint, Error> {
+    resource_index(program, kind.block_interface(), name)
+}
+
+// Assigns the block to a binding index, for blocks without layout(binding = ...)
+pub fn set_block_binding(
+    program: GLuint,
+    kind: BlockKind,
+    name: &str,
+    binding: GLuint,
+) -> Result<(), Error> {
+    let index = block_index(program, kind, name)?;
+    unsafe {
+        match kind {
+            BlockKind::Uniform => gl::UniformBlockBinding(program, index, binding),
+            BlockKind::Storage => gl::ShaderStorageBlockBinding(program, index, binding),
+        }
+    }
+    check_gl()
+}
+
+pub fn block_binding(program: GLuint, kind: BlockKind, name: &str) -> Result<GLuint, Error> {
+    let index = block_index(program, kind, name)?;
+    let binding = resource_property(program, kind.block_interface(), index, gl::BUFFER_BINDING)?;
+    Ok(binding as GLuint)
+}
+
+// Minimum buffer size in bytes, as laid out by the driver
+pub fn block_data_size(program: GLuint, kind: BlockKind, name: &str) -> Result<usize, Error> {
+    let index = block_index(program, kind, name)?;
+    let size = resource_property(program, kind.block_interface(), index, gl::BUFFER_DATA_SIZE)?;
+    Ok(size as usize)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockLayout {
+    Std140,
+    Std430,
+}
+
+// Scalar is float, int, uint or bool, vectors and matrices have the same layout for all of them.
+// Matrices are column-major.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlslType {
+    Scalar,
+    Vec2,
+    Vec3,
+    Vec4,
+    Mat2,
+    Mat3,
+    Mat4,
+}
+
+impl GlslType {
+    // (alignment, size) of a single non-array value
+    fn align_size(self, layout: BlockLayout) -> (usize, usize) {
+        let column = |rows: usize| {
+            let (align, size) = match rows {
+                2 => GlslType::Vec2.align_size(layout),
+                3 => GlslType::Vec3.align_size(layout),
+                _ => GlslType::Vec4.align_size(layout),
+            };
+            // matrices are laid out like arrays of column vectors
+            array_align_stride(layout, align, size)
+        };
+        match self {
+            GlslType::Scalar => (4, 4),
+            GlslType::Vec2 => (8, 8),
+            GlslType::Vec3 => (16, 12),
+            GlslType::Vec4 => (16, 16),
+            GlslType::Mat2 => {
+                let (align, stride) = column(2);
+                (align, stride * 2)
+            }
+            GlslType::Mat3 => {
+                let (align, stride) = column(3);
+                (align, stride * 3)
+            }
+            GlslType::Mat4 => {
+                let (align, stride) = column(4);
+                (align, stride * 4)
+            }
+        }
+    }
+}
+
+fn round_up(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+// std140 rounds array elements up to the alignment of a vec4
+fn array_align_stride(layout: BlockLayout, align: usize, size: usize) -> (usize, usize) {
+    let align = match layout {
+        BlockLayout::Std140 => round_up(align, 16),
+        BlockLayout::Std430 => align,
+    };
+    (align, round_up(size, align))
+}
+
+// A member of a block and the byte offset of the matching field in the Rust struct
+#[derive(Clone, Debug)]
+pub struct BlockField<'a> {
+    pub name: &'a str,
+    pub ty: GlslType,
+    // element count for arrays
+    pub array: Option<usize>,
+    pub offset: usize,
+}
+
+// Builds a BlockField from a field of a #[repr(C)] struct:
+// block_field!(Params, color, GlslType::Vec4) or block_field!(Params, weights, GlslType::Scalar, 8)
+#[macro_export]
+macro_rules! block_field {
+    ($struct:ty, $field:ident, $ty:expr) => {
+        $crate::block::BlockField {
+            name: stringify!($field),
+            ty: $ty,
+            array: None,
+            offset: std::mem::offset_of!($struct, $field),
+        }
+    };
+    ($struct:ty, $field:ident, $ty:expr, $count:expr) => {
+        $crate::block::BlockField {
+            name: stringify!($field),
+            ty: $ty,
+            array: Some($count),
+            offset: std::mem::offset_of!($struct, $field),
+        }
+    };
+}
+
+// Offsets of the fields in declaration order and the total size of the block
+pub fn layout_offsets(layout: BlockLayout, fields: &[BlockField]) -> (Vec<usize>, usize) {
+    let mut offsets = Vec::with_capacity(fields.len());
+    let mut end = 0;
+    let mut block_align = match layout {
+        BlockLayout::Std140 => 16,
+        BlockLayout::Std430 => 1,
+    };
+    for field in fields {
+        let (mut align, mut size) = field.ty.align_size(layout);
+        if let Some(count) = field.array {
+            let (array_align, stride) = array_align_stride(layout, align, size);
+            align = array_align;
+            size = stride * count;
+        }
+        let offset = round_up(end, align);
+        offsets.push(offset);
+        end = offset + size;
+        block_align = block_align.max(align);
+    }
+    (offsets, round_up(end, block_align))
+}
+
+// Checks that the fields of T are where layout puts them, and that T has the size of the block
+pub fn check_layout<T>(layout: BlockLayout, fields: &[BlockField]) -> Result<(), Error> {
+    let (offsets, size) = layout_offsets(layout, fields);
+    for (field, expected) in fields.iter().zip(offsets) {
+        if field.offset != expected {
+            return Err(format!(
+                "Field {} of {} is at offset {}, but {:?} puts it at {}",
+                field.name,
+                std::any::type_name::<T>(),
+                field.offset,
+                layout,
+                expected
+            )
+            .into());
+        }
+    }
+    if std::mem::size_of::<T>() != size {
+        return Err(format!(
+            "{} is {} bytes, but the {:?} block is {} bytes",
+            std::any::type_name::<T>(),
+            std::mem::size_of::<T>(),
+            layout,
+            size
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// Checks the fields against the offsets the driver reports for a block in a linked program.
+// instance is the instance name of the block, e.g. Some("params") for
+// `uniform Params { ... } params;` and None for `uniform Params { ... };`. GL only qualifies
+// member names (with the block name, Params.member) when the block has an instance name.
+pub fn check_block_offsets(
+    program: GLuint,
+    kind: BlockKind,
+    block: &str,
+    instance: Option<&str>,
+    fields: &[BlockField],
+) -> Result<(), Error> {
+    let interface = kind.variable_interface();
+    for field in fields {
+        let mut name = match instance {
+            Some(_) => format!("{}.{}", block, field.name),
+            None => field.name.to_string(),
+        };
+        if field.array.is_some() {
+            name.push_str("[0]");
+        }
+        let index = resource_index(program, interface, &name)?;
+        let offset = resource_property(program, interface, index, gl::OFFSET)? as usize;
+        if offset != field.offset {
+            return Err(format!(
+                "Field {} is at offset {} in the Rust struct, but at {} in block {}",
+                field.name, field.offset, offset, block
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(layout: BlockLayout, types: &[(GlslType, Option<usize>)]) -> (Vec<usize>, usize) {
+        let fields = types
+            .iter()
+            .map(|&(ty, array)| BlockField {
+                name: "",
+                ty,
+                array,
+                offset: 0,
+            })
+            .collect::<Vec<_>>();
+        layout_offsets(layout, &fields)
+    }
+
+    #[test]
+    fn vec3_alignment() {
+        for &block_layout in &[BlockLayout::Std140, BlockLayout::Std430] {
+            let fields = [(GlslType::Scalar, None), (GlslType::Vec3, None)];
+            assert_eq!(layout(block_layout, &fields), (vec![0, 16], 32));
+            // a scalar can go in the last 4 bytes of a vec3
+            let fields = [(GlslType::Vec3, None), (GlslType::Scalar, None)];
+            assert_eq!(layout(block_layout, &fields), (vec![0, 12], 16));
+        }
+    }
+
+    #[test]
+    fn array_stride() {
+        let fields = [(GlslType::Scalar, Some(4)), (GlslType::Scalar, None)];
+        assert_eq!(layout(BlockLayout::Std140, &fields), (vec![0, 64], 80));
+        assert_eq!(layout(BlockLayout::Std430, &fields), (vec![0, 16], 20));
+        let fields = [(GlslType::Scalar, None), (GlslType::Vec2, Some(2))];
+        assert_eq!(layout(BlockLayout::Std140, &fields), (vec![0, 16], 48));
+        assert_eq!(layout(BlockLayout::Std430, &fields), (vec![0, 8], 24));
+    }
+
+    #[test]
+    fn matrices() {
+        let fields = [(GlslType::Scalar, None), (GlslType::Mat2, None)];
+        assert_eq!(layout(BlockLayout::Std140, &fields), (vec![0, 16], 48));
+        assert_eq!(layout(BlockLayout::Std430, &fields), (vec![0, 8], 24));
+        for &block_layout in &[BlockLayout::Std140, BlockLayout::Std430] {
+            let fields = [(GlslType::Mat3, None), (GlslType::Mat4, None)];
+            assert_eq!(layout(block_layout, &fields), (vec![0, 48], 112));
+        }
+    }
+
+    #[test]
+    fn check_struct_layout() {
+        #[repr(C)]
+        struct Padded {
+            scale: f32,
+            _pad: [f32; 3],
+            color: [f32; 4],
+        }
+        #[repr(C)]
+        struct Packed {
+            scale: f32,
+            color: [f32; 4],
+        }
+        let fields = [
+            crate::block_field!(Padded, scale, GlslType::Scalar),
+            crate::block_field!(Padded, color, GlslType::Vec4),
+        ];
+        assert!(check_layout::<Padded>(BlockLayout::Std140, &fields).is_ok());
+        let fields = [
+            crate::block_field!(Packed, scale, GlslType::Scalar),
+            crate::block_field!(Packed, color, GlslType::Vec4),
+        ];
+        assert!(check_layout::<Packed>(BlockLayout::Std430, &fields).is_err());
+    }
+}
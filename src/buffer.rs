@@ -0,0 +1,269 @@
+use crate::{check_gl, Error};
+use gl::types::*;
+use std::{
+    ffi::c_void,
+    marker::PhantomData,
+    mem::{size_of, size_of_val},
+};
+
+// Plain old data, copied to and from GPU memory byte by byte. Unsafe to implement: only for
+// types without padding, pointers or invalid bit patterns, e.g. #[repr(C)] structs made only of
+// Pod fields with no gaps between them.
+#[allow(clippy::missing_safety_doc)]
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for i8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for i64 {}
+unsafe impl Pod for f32 {}
+unsafe impl Pod for f64 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+pub struct Buffer<T: Pod> {
+    pub id: GLuint,
+    len: usize,
+    // Some for immutable storage created with glNamedBufferStorage
+    storage_flags: Option<GLbitfield>,
+    _t: PhantomData<T>,
+}
+
+impl<T: Pod> Buffer<T> {
+    pub fn new() -> Result<Self, Error> {
+        let mut id = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut id);
+            check_gl()?;
+        }
+        Ok(Self {
+            id,
+            len: 0,
+            storage_flags: None,
+            _t: PhantomData,
+        })
+    }
+
+    pub fn with_data(data: &[T], usage: GLenum) -> Result<Self, Error> {
+        let mut buffer = Self::new()?;
+        buffer.set_data(data, usage)?;
+        Ok(buffer)
+    }
+
+    // Immutable storage for len elements, initialized from data if given. flags is a combination
+    // of gl::DYNAMIC_STORAGE_BIT (needed for update_range), gl::MAP_READ_BIT, gl::MAP_WRITE_BIT,
+    // gl::MAP_PERSISTENT_BIT, gl::MAP_COHERENT_BIT and gl::CLIENT_STORAGE_BIT.
+    pub fn with_storage(len: usize, data: Option<&[T]>, flags: GLbitfield) -> Result<Self, Error> {
+        if let Some(data) = data {
+            if data.len() != len {
+                return Err(format!(
+                    "Buffer storage data length {} does not match length {}",
+                    data.len(),
+                    len
+                )
+                .into());
+            }
+        }
+        let mut buffer = Self::new()?;
+        let ptr = data.map_or(std::ptr::null(), |data| data.as_ptr() as *const c_void);
+        unsafe {
+            gl::NamedBufferStorage(buffer.id, (len * size_of::<T>()) as GLsizeiptr, ptr, flags);
+            check_gl()?;
+        }
+        buffer.len = len;
+        buffer.storage_flags = Some(flags);
+        Ok(buffer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn byte_size(&self) -> usize {
+        self.len * size_of::<T>()
+    }
+
+    pub fn storage_flags(&self) -> Option<GLbitfield> {
+        self.storage_flags
+    }
+
+    // Reallocates the buffer, only possible for buffers without immutable storage
+    pub fn set_data(&mut self, data: &[T], usage: GLenum) -> Result<(), Error> {
+        // usage must be: GL_STREAM_DRAW, GL_STREAM_READ, GL_STREAM_COPY, GL_STATIC_DRAW, GL_STATIC_READ, GL_STATIC_COPY, GL_DYNAMIC_DRAW, GL_DYNAMIC_READ, or GL_DYNAMIC_COPY
+        if self.storage_flags.is_some() {
+            return Err("Cannot set_data on a buffer with immutable storage".into());
+        }
+        unsafe {
+            gl::NamedBufferData(
+                self.id,
+                size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+                usage,
+            );
+            check_gl()?;
+        }
+        self.len = data.len();
+        Ok(())
+    }
+
+    fn check_range(&self, offset: usize, count: usize) -> Result<(), Error> {
+        match offset.checked_add(count) {
+            Some(end) if end <= self.len => Ok(()),
+            _ => Err(format!(
+                "Buffer range {}+{} out of range (length {})",
+                offset, count, self.len
+            )
+            .into()),
+        }
+    }
+
+    // offset is in elements
+    pub fn update_range(&mut self, offset: usize, data: &[T]) -> Result<(), Error> {
+        self.check_range(offset, data.len())?;
+        unsafe {
+            gl::NamedBufferSubData(
+                self.id,
+                (offset * size_of::<T>()) as GLintptr,
+                size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+            );
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    // offset is in elements
+    pub fn read_range(&self, offset: usize, count: usize) -> Result<Vec<T>, Error> {
+        self.check_range(offset, count)?;
+        let mut result = Vec::<T>::with_capacity(count);
+        unsafe {
+            gl::GetNamedBufferSubData(
+                self.id,
+                (offset * size_of::<T>()) as GLintptr,
+                (count * size_of::<T>()) as GLsizeiptr,
+                result.as_mut_ptr() as *mut c_void,
+            );
+            check_gl()?;
+            // T: Pod, so whatever GL wrote is a valid T
+            result.set_len(count);
+        }
+        Ok(result)
+    }
+
+    pub fn read(&self) -> Result<Vec<T>, Error> {
+        self.read_range(0, self.len)
+    }
+
+    fn bind_base(&self, target: GLenum, index: GLuint) -> Result<(), Error> {
+        unsafe {
+            gl::BindBufferBase(target, index, self.id);
+            check_gl()
+        }
+    }
+
+    // offset and count are in elements, offset * size_of::<T>() has to be a multiple of
+    // alignment_property (e.g. gl::SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT)
+    fn bind_range(
+        &self,
+        target: GLenum,
+        alignment_property: GLenum,
+        index: GLuint,
+        offset: usize,
+        count: usize,
+    ) -> Result<(), Error> {
+        self.check_range(offset, count)?;
+        let mut alignment = 0;
+        unsafe {
+            gl::GetIntegerv(alignment_property, &mut alignment);
+            check_gl()?;
+        }
+        let byte_offset = offset * size_of::<T>();
+        if alignment > 0 && !byte_offset.is_multiple_of(alignment as usize) {
+            return Err(format!(
+                "Buffer binding offset {} is not a multiple of the required alignment {}",
+                byte_offset, alignment
+            )
+            .into());
+        }
+        unsafe {
+            gl::BindBufferRange(
+                target,
+                index,
+                self.id,
+                byte_offset as GLintptr,
+                (count * size_of::<T>()) as GLsizeiptr,
+            );
+            check_gl()
+        }
+    }
+
+    // binding = index in the shader, e.g. layout(std430, binding = 0) buffer
+    pub fn bind_storage(&self, index: GLuint) -> Result<(), Error> {
+        self.bind_base(gl::SHADER_STORAGE_BUFFER, index)
+    }
+
+    pub fn bind_storage_range(
+        &self,
+        index: GLuint,
+        offset: usize,
+        count: usize,
+    ) -> Result<(), Error> {
+        self.bind_range(
+            gl::SHADER_STORAGE_BUFFER,
+            gl::SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT,
+            index,
+            offset,
+            count,
+        )
+    }
+
+    // binding = index in the shader, e.g. layout(std140, binding = 0) uniform
+    pub fn bind_uniform(&self, index: GLuint) -> Result<(), Error> {
+        self.bind_base(gl::UNIFORM_BUFFER, index)
+    }
+
+    pub fn bind_uniform_range(
+        &self,
+        index: GLuint,
+        offset: usize,
+        count: usize,
+    ) -> Result<(), Error> {
+        self.bind_range(
+            gl::UNIFORM_BUFFER,
+            gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT,
+            index,
+            offset,
+            count,
+        )
+    }
+}
+
+pub fn unbind_storage(index: GLuint) -> Result<(), Error> {
+    unsafe {
+        gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, index, 0);
+        check_gl()
+    }
+}
+
+pub fn unbind_uniform(index: GLuint) -> Result<(), Error> {
+    unsafe {
+        gl::BindBufferBase(gl::UNIFORM_BUFFER, index, 0);
+        check_gl()
+    }
+}
+
+impl<T: Pod> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+            check_gl().expect("Failed to delete buffer in drop impl");
+        }
+    }
+}
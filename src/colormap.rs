@@ -0,0 +1,180 @@
+use crate::{
+    check_gl,
+    render_text::TextRenderer,
+    render_texture::TextureRenderer,
+    texture::{CpuTexture, Texture},
+    Error, Rect,
+};
+use gl::types::*;
+
+// Polynomial fits of the matplotlib colormaps, coefficients of t^0 to t^6 for r, g, b
+const VIRIDIS: [[f32; 3]; 7] = [
+    [0.277_727_33, 0.005_407_344_5, 0.334_099_8],
+    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+    [-4.634_230_5, -5.799_101, -19.332_441],
+    [6.228_27, 14.179_933, 56.690_55],
+    [4.776_385, -13.745_145, -65.353_03],
+    [-5.435_456, 4.645_852_6, 26.312_435],
+];
+
+const MAGMA: [[f32; 3]; 7] = [
+    [-0.002_136_485, -0.000_749_655_05, -0.005_386_128],
+    [0.251_660_54, 0.677_523_2, 2.494_026_6],
+    [8.353_717, -3.577_719_5, 0.314_467_9],
+    [-27.668_733, 14.264_731, -13.649_213],
+    [52.176_14, -27.943_606, 12.944_169],
+    [-50.768_524, 29.046_583, 4.234_153],
+    [18.655_705, -11.489_774, -5.601_961_5],
+];
+
+const INFERNO: [[f32; 3]; 7] = [
+    [0.000_218_940_37, 0.001_651_004_6, -0.019_480_899],
+    [0.106_513_42, 0.563_956_4, 3.932_712_4],
+    [11.602_493, -3.972_854, -15.942_394],
+    [-41.703_995, 17.436_4, 44.354_145],
+    [77.162_94, -33.402_36, -81.807_31],
+    [-71.319_43, 32.626_064, 73.209_52],
+    [25.131_126, -12.242_669, -23.070_326],
+];
+
+// Polynomial fit of turbo, coefficients of t^0 to t^5 for r, g, b
+const TURBO: [[f32; 3]; 6] = [
+    [0.135_721_38, 0.091_402_61, 0.106_673_3],
+    [4.615_392_6, 2.194_188_4, 12.641_946],
+    [-42.660_324, 4.842_966_6, -60.582_047],
+    [132.131_08, -14.185_033, 110.362_77],
+    [-152.942_4, 4.277_299, -89.903_11],
+    [59.286_38, 2.829_566, 27.348_25],
+];
+
+fn polynomial(coefficients: &[[f32; 3]], t: f32) -> [f32; 4] {
+    let mut rgb = [0.0; 3];
+    for coefficient in coefficients.iter().rev() {
+        for (value, c) in rgb.iter_mut().zip(coefficient) {
+            *value = *value * t + c;
+        }
+    }
+    [
+        rgb[0].clamp(0.0, 1.0),
+        rgb[1].clamp(0.0, 1.0),
+        rgb[2].clamp(0.0, 1.0),
+        1.0,
+    ]
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Colormap {
+    Grayscale,
+    Viridis,
+    Magma,
+    Inferno,
+    Turbo,
+    // evenly spaced colors, linearly interpolated
+    Custom(Vec<[f32; 4]>),
+}
+
+impl Colormap {
+    pub const LUT_SIZE: usize = 256;
+
+    // t is clamped to [0, 1]
+    pub fn color(&self, t: f32) -> [f32; 4] {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Colormap::Grayscale => [t, t, t, 1.0],
+            Colormap::Viridis => polynomial(&VIRIDIS, t),
+            Colormap::Magma => polynomial(&MAGMA, t),
+            Colormap::Inferno => polynomial(&INFERNO, t),
+            Colormap::Turbo => polynomial(&TURBO, t),
+            Colormap::Custom(colors) => {
+                assert!(!colors.is_empty(), "Custom colormap without colors");
+                let pos = t * (colors.len() - 1) as f32;
+                let index = (pos as usize).min(colors.len() - 1);
+                let next = (index + 1).min(colors.len() - 1);
+                let frac = pos - index as f32;
+                let mut result = [0.0; 4];
+                for (i, value) in result.iter_mut().enumerate() {
+                    *value = colors[index][i] * (1.0 - frac) + colors[next][i] * frac;
+                }
+                result
+            }
+        }
+    }
+
+    pub fn lut(&self, size: usize) -> CpuTexture<[f32; 4]> {
+        let last = (size.max(2) - 1) as f32;
+        CpuTexture::from_fn((size, 1), |(x, _)| self.color(x as f32 / last))
+    }
+
+    // A LUT_SIZE x 1 texture with linear filtering, for RenderBuilder::colormap
+    pub fn texture(&self) -> Result<Texture<[f32; 4]>, Error> {
+        let mut texture = Texture::new((Self::LUT_SIZE, 1))?;
+        texture.upload(&self.lut(Self::LUT_SIZE))?;
+        unsafe {
+            gl::TextureParameteri(texture.id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TextureParameteri(texture.id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TextureParameteri(texture.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TextureParameteri(texture.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            check_gl()?;
+        }
+        Ok(texture)
+    }
+}
+
+fn tick_label(value: f32, step: f32) -> String {
+    let decimals = if step > 0.0 {
+        (-step.log10().floor()).max(0.0) as usize
+    } else {
+        0
+    };
+    format!("{:.*}", decimals, value)
+}
+
+// Draws lut horizontally into rect, with ticks and labels for range below it. Returns the
+// rectangle covering the bar and its labels.
+#[allow(clippy::too_many_arguments)]
+pub fn render_colorbar(
+    renderer: &TextureRenderer,
+    text: &mut TextRenderer,
+    lut: &Texture<[f32; 4]>,
+    rect: Rect<usize>,
+    range: (f32, f32),
+    ticks: usize,
+    color: [f32; 4],
+    screen_size: (usize, usize),
+) -> Result<Rect<usize>, Error> {
+    let screen_size_f32 = (screen_size.0 as f32, screen_size.1 as f32);
+    renderer
+        .render(lut, screen_size_f32)
+        .dst(rect.to_f32())
+        .go()?;
+    renderer.rect(rect.clone(), color, screen_size_f32)?;
+    let mut bottom = rect.bottom();
+    let ticks = ticks.max(2);
+    let step = (range.1 - range.0) / (ticks - 1) as f32;
+    for tick in 0..ticks {
+        let x = rect.x + (rect.width * tick) / (ticks - 1);
+        let tick_end = rect.bottom() + text.spacing / 4;
+        renderer.line_y(x, rect.bottom(), tick_end, color, screen_size_f32)?;
+        let label = tick_label(range.0 + step * tick as f32, step.abs());
+        let label_rect = text.render(renderer, &label, color, (x, tick_end), screen_size)?;
+        bottom = bottom.max(label_rect.bottom());
+    }
+    Ok(Rect::new(rect.x, rect.y, rect.width, bottom - rect.y))
+}
+
+pub(crate) const COLORMAP_GLSL: &str = "
+uniform sampler2D colormap;
+// -1 when disabled
+uniform int colormap_channel;
+
+vec4 apply_colormap(vec4 color)
+{
+    if (colormap_channel < 0) {
+        return color;
+    }
+    float size = float(textureSize(colormap, 0).x);
+    float t = clamp(color[colormap_channel], 0.0, 1.0);
+    return texture(colormap, vec2((t * (size - 1.0) + 0.5) / size, 0.5));
+}
+";
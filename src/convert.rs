@@ -0,0 +1,303 @@
+use crate::texture::CpuTexture;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelKind {
+    // normalized integer, raw values 0..=max represent 0.0..=1.0
+    Unorm(f64),
+    Float,
+    // unnormalized integer, like R32UI
+    Integer,
+    // unnormalized signed integer, like R32I
+    SignedInteger,
+}
+
+// Pixel types that can be converted to each other with CpuTexture::convert. Channels are raw
+// values in the domain of KIND, single-channel types only use the first one.
+pub trait PixelConvert: Sized {
+    const CHANNELS: usize;
+    const KIND: PixelKind;
+    fn to_channels(&self) -> [f64; 4];
+    // values are already rounded and clamped to the range of the type
+    fn from_channels(channels: [f64; 4]) -> Self;
+}
+
+impl PixelConvert for [f32; 4] {
+    const CHANNELS: usize = 4;
+    const KIND: PixelKind = PixelKind::Float;
+    fn to_channels(&self) -> [f64; 4] {
+        [
+            self[0] as f64,
+            self[1] as f64,
+            self[2] as f64,
+            self[3] as f64,
+        ]
+    }
+    fn from_channels(channels: [f64; 4]) -> Self {
+        [
+            channels[0] as f32,
+            channels[1] as f32,
+            channels[2] as f32,
+            channels[3] as f32,
+        ]
+    }
+}
+
+impl PixelConvert for [u8; 4] {
+    const CHANNELS: usize = 4;
+    const KIND: PixelKind = PixelKind::Unorm(u8::MAX as f64);
+    fn to_channels(&self) -> [f64; 4] {
+        [
+            self[0] as f64,
+            self[1] as f64,
+            self[2] as f64,
+            self[3] as f64,
+        ]
+    }
+    fn from_channels(channels: [f64; 4]) -> Self {
+        [
+            channels[0] as u8,
+            channels[1] as u8,
+            channels[2] as u8,
+            channels[3] as u8,
+        ]
+    }
+}
+
+impl PixelConvert for u16 {
+    const CHANNELS: usize = 1;
+    const KIND: PixelKind = PixelKind::Unorm(u16::MAX as f64);
+    fn to_channels(&self) -> [f64; 4] {
+        [*self as f64, 0.0, 0.0, 0.0]
+    }
+    fn from_channels(channels: [f64; 4]) -> Self {
+        channels[0] as u16
+    }
+}
+
+impl PixelConvert for f32 {
+    const CHANNELS: usize = 1;
+    const KIND: PixelKind = PixelKind::Float;
+    fn to_channels(&self) -> [f64; 4] {
+        [*self as f64, 0.0, 0.0, 0.0]
+    }
+    fn from_channels(channels: [f64; 4]) -> Self {
+        channels[0] as f32
+    }
+}
+
+impl PixelConvert for u32 {
+    const CHANNELS: usize = 1;
+    const KIND: PixelKind = PixelKind::Integer;
+    fn to_channels(&self) -> [f64; 4] {
+        [*self as f64, 0.0, 0.0, 0.0]
+    }
+    fn from_channels(channels: [f64; 4]) -> Self {
+        channels[0] as u32
+    }
+}
+
+impl PixelConvert for i32 {
+    const CHANNELS: usize = 1;
+    const KIND: PixelKind = PixelKind::SignedInteger;
+    fn to_channels(&self) -> [f64; 4] {
+        [*self as f64, 0.0, 0.0, 0.0]
+    }
+    fn from_channels(channels: [f64; 4]) -> Self {
+        channels[0] as i32
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ConvertOptions {
+    // weights of r, g, b when collapsing to a single channel
+    pub luminance: [f64; 3],
+    // ordered dithering when rounding to a normalized integer type
+    pub dither: bool,
+}
+
+impl ConvertOptions {
+    pub const REC709: [f64; 3] = [0.2126, 0.7152, 0.0722];
+    pub const REC601: [f64; 3] = [0.299, 0.587, 0.114];
+    pub const AVERAGE: [f64; 3] = [1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0];
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            luminance: Self::REC709,
+            dither: false,
+        }
+    }
+}
+
+const BAYER4: [[f64; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+// Normalization rules:
+// * unorm <-> float: raw / max, value * max
+// * unorm <-> integer and integer <-> integer: raw values are kept (saturating)
+// * float <-> integer: values are kept, rounded when converting to integer
+fn convert_channel(
+    value: f64,
+    from: PixelKind,
+    to: PixelKind,
+    dither_offset: f64,
+    alpha: bool,
+) -> f64 {
+    match to {
+        PixelKind::Float => match from {
+            PixelKind::Unorm(max) => value / max,
+            _ => value,
+        },
+        PixelKind::Unorm(to_max) => {
+            let raw = match from {
+                PixelKind::Unorm(from_max) if from_max != to_max => value * to_max / from_max,
+                PixelKind::Float => value * to_max,
+                _ => value,
+            };
+            let dither = if alpha { 0.0 } else { dither_offset };
+            (raw + dither).round().clamp(0.0, to_max)
+        }
+        PixelKind::Integer => value.round().clamp(0.0, u32::MAX as f64),
+        PixelKind::SignedInteger => value.round().clamp(i32::MIN as f64, i32::MAX as f64),
+    }
+}
+
+fn one(kind: PixelKind) -> f64 {
+    match kind {
+        PixelKind::Unorm(max) => max,
+        PixelKind::Float | PixelKind::Integer | PixelKind::SignedInteger => 1.0,
+    }
+}
+
+pub fn convert_pixel<T: PixelConvert, U: PixelConvert>(
+    pixel: &T,
+    pos: (usize, usize),
+    options: &ConvertOptions,
+) -> U {
+    let channels = pixel.to_channels();
+    let dither_offset = if options.dither {
+        (BAYER4[pos.1 % 4][pos.0 % 4] + 0.5) / 16.0 - 0.5
+    } else {
+        0.0
+    };
+    let mut result = [0.0; 4];
+    match (T::CHANNELS, U::CHANNELS) {
+        (1, 4) => {
+            let gray = convert_channel(channels[0], T::KIND, U::KIND, dither_offset, false);
+            result = [gray, gray, gray, one(U::KIND)];
+        }
+        (4, 1) => {
+            let weights = options.luminance;
+            let luminance =
+                channels[0] * weights[0] + channels[1] * weights[1] + channels[2] * weights[2];
+            result[0] = convert_channel(luminance, T::KIND, U::KIND, dither_offset, false);
+        }
+        (channel_count, _) => {
+            for (i, (out, &value)) in result.iter_mut().zip(&channels).enumerate() {
+                if i < channel_count {
+                    *out = convert_channel(value, T::KIND, U::KIND, dither_offset, i == 3);
+                }
+            }
+        }
+    }
+    U::from_channels(result)
+}
+
+// Rounds and clamps raw channel values computed in the domain of T, e.g. by a filter, to the
+// range of T
+pub(crate) fn saturate<T: PixelConvert>(channels: [f64; 4]) -> T {
+    let mut result = [0.0; 4];
+    for (out, &value) in result.iter_mut().zip(&channels) {
+        *out = convert_channel(value, T::KIND, T::KIND, 0.0, true);
+    }
+    T::from_channels(result)
+}
+
+impl<T: PixelConvert> CpuTexture<T> {
+    pub fn convert<U: PixelConvert>(&self) -> CpuTexture<U> {
+        self.convert_with(&ConvertOptions::default())
+    }
+
+    pub fn convert_with<U: PixelConvert>(&self, options: &ConvertOptions) -> CpuTexture<U> {
+        let pixels = self
+            .iter_index()
+            .map(|pos| convert_pixel(&self[pos], pos, options))
+            .collect();
+        CpuTexture::new(pixels, self.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert<T: PixelConvert, U: PixelConvert>(pixel: T) -> U {
+        convert_pixel(&pixel, (0, 0), &ConvertOptions::default())
+    }
+
+    #[test]
+    fn unorm_scaling() {
+        assert_eq!(convert::<_, u16>([128u8, 128, 128, 255]), 32896);
+        assert_eq!(convert::<_, u16>([255u8, 255, 255, 255]), u16::MAX);
+        assert_eq!(convert::<_, [u8; 4]>(32896u16), [128, 128, 128, 255]);
+        assert_eq!(convert::<_, [u8; 4]>(u16::MAX), [255; 4]);
+        assert_eq!(convert::<_, f32>(u16::MAX), 1.0);
+        assert_eq!(
+            convert::<_, [f32; 4]>([0u8, 51, 255, 0]),
+            [0.0, 0.2, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn float_rounds_and_clamps() {
+        assert_eq!(
+            convert::<_, [u8; 4]>([0.5f32, -1.0, 2.0, 1.0]),
+            [128, 0, 255, 255]
+        );
+        assert_eq!(convert::<_, u16>(-0.25f32), 0);
+        assert_eq!(convert::<_, u32>(2.5f32), 3);
+        assert_eq!(convert::<_, i32>(-2.4f32), -2);
+        assert_eq!(convert::<_, f32>(7u32), 7.0);
+    }
+
+    #[test]
+    fn integer_saturates() {
+        assert_eq!(convert::<_, u32>(-5i32), 0);
+        assert_eq!(convert::<_, i32>(u32::MAX), i32::MAX);
+        assert_eq!(convert::<_, u16>(70000u32), u16::MAX);
+        // raw values are kept between integer and unorm types
+        assert_eq!(convert::<_, u32>(1234u16), 1234);
+        assert_eq!(convert::<_, i32>(-7i32), -7);
+    }
+
+    #[test]
+    fn luminance() {
+        let options = ConvertOptions {
+            luminance: ConvertOptions::AVERAGE,
+            dither: false,
+        };
+        let gray: f32 = convert_pixel(&[0.3f32, 0.6, 0.9, 0.0], (0, 0), &options);
+        assert!((gray - 0.6).abs() < 1e-6);
+        let red: f32 = convert::<_, f32>([1.0f32, 0.0, 0.0, 1.0]);
+        assert!((red - ConvertOptions::REC709[0] as f32).abs() < 1e-6);
+    }
+
+    #[test]
+    fn dither_averages_to_value() {
+        let texture = CpuTexture::new_val(0.3f32, (4, 4));
+        let options = ConvertOptions {
+            dither: true,
+            ..Default::default()
+        };
+        let dithered = texture.convert_with::<[u8; 4]>(&options);
+        let mean = dithered.data().iter().map(|p| p[0] as f64).sum::<f64>() / 16.0;
+        assert!((mean - 0.3 * 255.0).abs() < 0.5);
+        // alpha is never dithered
+        assert!(dithered.data().iter().all(|p| p[3] == 255));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    check_gl, create_compute_program,
+    texture::{
+        create_view, download_format_type, upload_format_type, with_unpack_row_length, CpuTexture,
+        Texture, TextureType,
+    },
+    Error, GLSL_VERSION,
+};
+use gl::types::*;
+use std::{f32::consts::PI, ffi::c_void, marker::PhantomData};
+
+// Face order matches GL_TEXTURE_CUBE_MAP_POSITIVE_X + index
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    // uv in [-1, 1], with v pointing down the face image. Must match face_direction in
+    // CUBE_GLSL.
+    pub fn direction(self, u: f32, v: f32) -> [f32; 3] {
+        match self {
+            CubeFace::PositiveX => [1.0, -v, -u],
+            CubeFace::NegativeX => [-1.0, -v, u],
+            CubeFace::PositiveY => [u, 1.0, v],
+            CubeFace::NegativeY => [u, -1.0, -v],
+            CubeFace::PositiveZ => [u, -v, 1.0],
+            CubeFace::NegativeZ => [-u, -v, -1.0],
+        }
+    }
+}
+
+pub struct CubeTexture<T: TextureType> {
+    pub id: GLuint,
+    // width and height of each face
+    pub size: usize,
+    _t: PhantomData<T>,
+}
+
+impl<T: TextureType> CubeTexture<T> {
+    pub fn new(size: usize) -> Result<Self, Error> {
+        let format = T::internalformat();
+        let mut texture = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_CUBE_MAP, 1, &mut texture);
+            check_gl()?;
+            gl::TextureStorage2D(texture, 1, format, size as _, size as _);
+            check_gl()?;
+            gl::TextureParameteri(texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            check_gl()?;
+            gl::TextureParameteri(texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            check_gl()?;
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+            check_gl()?;
+        }
+        Ok(Self {
+            id: texture,
+            size,
+            _t: PhantomData,
+        })
+    }
+
+    pub fn download_face(&mut self, face: CubeFace) -> Result<CpuTexture<T>, Error> {
+        let mut pixels = vec![T::default(); self.size * self.size];
+        let buf_size = T::size() * pixels.len();
+        let (format, type_) = download_format_type::<T>()?;
+        unsafe {
+            gl::GetTextureSubImage(
+                self.id,
+                0,
+                0,
+                0,
+                face.index() as _,
+                self.size as _,
+                self.size as _,
+                1,
+                format,
+                type_,
+                buf_size as GLsizei,
+                pixels.as_mut_ptr() as *mut _,
+            );
+            check_gl()?;
+        }
+        Ok(CpuTexture::new(pixels, (self.size, self.size)))
+    }
+
+    pub fn upload_face(
+        &mut self,
+        face: CubeFace,
+        cpu_texture: &CpuTexture<T>,
+    ) -> Result<(), Error> {
+        assert_eq!((self.size, self.size), cpu_texture.size);
+        let (format, type_) = upload_format_type::<T>()?;
+        with_unpack_row_length(self.size, || unsafe {
+            gl::TextureSubImage3D(
+                self.id,
+                0,
+                0,
+                0,
+                face.index() as _,
+                self.size as _,
+                self.size as _,
+                1,
+                format,
+                type_,
+                cpu_texture.data().as_ptr() as *const c_void,
+            );
+        })
+    }
+
+    // faces in CubeFace::ALL order
+    pub fn upload(&mut self, faces: &[CpuTexture<T>; 6]) -> Result<(), Error> {
+        for (&face, cpu_texture) in CubeFace::ALL.iter().zip(faces.iter()) {
+            self.upload_face(face, cpu_texture)?;
+        }
+        Ok(())
+    }
+
+    // A 2D texture sharing storage with one face of self, interpreted as U
+    pub fn face_view<U>(&self, face: CubeFace) -> Result<Texture<U>, Error>
+    where
+        U: TextureType<ViewClass = T::ViewClass>,
+    {
+        let layer = face.index() as u32;
+        let texture = create_view::<U>(self.id, 0..1, layer..layer + 1)?;
+        Texture::from_storage(texture, (self.size, self.size))
+    }
+
+    // Binds all six faces as a layered image (imageCube in glsl)
+    pub fn bind(&self, unit: usize) -> Result<(), Error> {
+        unsafe {
+            gl::BindImageTexture(
+                unit as GLuint,
+                self.id,
+                0,
+                gl::TRUE,
+                0,
+                gl::READ_WRITE,
+                T::internalformat(),
+            );
+            check_gl()
+        }
+    }
+}
+
+impl<T: TextureType> Drop for CubeTexture<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+        check_gl().expect("Failed to delete cube texture in drop impl");
+    }
+}
+
+// Returns (x, y) in [0, 1], y pointing down. Must match equirect_coord in CUBE_GLSL.
+fn equirect_coord(dir: [f32; 3]) -> (f32, f32) {
+    let len = (dir[0] * dir[0] + dir[1] * dir[1] + dir[2] * dir[2]).sqrt();
+    let lon = dir[0].atan2(-dir[2]);
+    let lat = (dir[1] / len).clamp(-1.0, 1.0).acos();
+    (lon / (2.0 * PI) + 0.5, lat / PI)
+}
+
+fn equirect_face<T: Clone>(
+    equirect: &CpuTexture<T>,
+    face: CubeFace,
+    face_size: usize,
+) -> CpuTexture<T> {
+    let mut pixels = Vec::with_capacity(face_size * face_size);
+    for y in 0..face_size {
+        for x in 0..face_size {
+            let u = (x as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+            let v = (y as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+            let (src_x, src_y) = equirect_coord(face.direction(u, v));
+            let src_x = (src_x * equirect.size.0 as f32).floor() as isize;
+            let src_y = (src_y * equirect.size.1 as f32).floor() as isize;
+            // longitude wraps around, latitude does not
+            let src_x = src_x.rem_euclid(equirect.size.0 as isize);
+            pixels.push(equirect.get_clamped(src_x, src_y).clone());
+        }
+    }
+    CpuTexture::new(pixels, (face_size, face_size))
+}
+
+// Nearest-neighbor resample of an equirectangular (longitude/latitude) image into six faces,
+// in CubeFace::ALL order.
+pub fn equirect_to_cube_faces<T: Clone>(
+    equirect: &CpuTexture<T>,
+    face_size: usize,
+) -> [CpuTexture<T>; 6] {
+    [
+        equirect_face(equirect, CubeFace::PositiveX, face_size),
+        equirect_face(equirect, CubeFace::NegativeX, face_size),
+        equirect_face(equirect, CubeFace::PositiveY, face_size),
+        equirect_face(equirect, CubeFace::NegativeY, face_size),
+        equirect_face(equirect, CubeFace::PositiveZ, face_size),
+        equirect_face(equirect, CubeFace::NegativeZ, face_size),
+    ]
+}
+
+pub struct EquirectConverter {
+    program: GLuint,
+}
+
+impl EquirectConverter {
+    pub fn new() -> Result<Self, Error> {
+        let program = create_compute_program(&[GLSL_VERSION, CUBE_GLSL, EQUIRECT_COMPUTE_SHADER])?;
+        if !program.success {
+            panic!("Failed to compile shader: {}", program.log);
+        }
+        Ok(Self {
+            program: program.shader,
+        })
+    }
+
+    // Samples src with its own filtering parameters, so set them to LINEAR for bilinear results
+    pub fn convert(
+        &self,
+        src: &Texture<[f32; 4]>,
+        dst: &mut CubeTexture<[f32; 4]>,
+    ) -> Result<(), Error> {
+        let groups = (dst.size as GLuint).div_ceil(8);
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::BindTextureUnit(0, src.id);
+            check_gl()?;
+            dst.bind(0)?;
+            gl::DispatchCompute(groups, groups, 6);
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT | gl::TEXTURE_UPDATE_BARRIER_BIT);
+            gl::BindTextureUnit(0, 0);
+            gl::UseProgram(0);
+            check_gl()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EquirectConverter {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+        }
+    }
+}
+
+pub(crate) const CUBE_GLSL: &str = "
+#define CUBE_PI 3.14159265358979323846
+
+vec3 face_direction(int face, vec2 uv)
+{
+    switch (face) {
+    case 0: return vec3(1, -uv.y, -uv.x);
+    case 1: return vec3(-1, -uv.y, uv.x);
+    case 2: return vec3(uv.x, 1, uv.y);
+    case 3: return vec3(uv.x, -1, -uv.y);
+    case 4: return vec3(uv.x, -uv.y, 1);
+    default: return vec3(-uv.x, -uv.y, -1);
+    }
+}
+
+vec2 equirect_coord(vec3 dir)
+{
+    dir = normalize(dir);
+    float lon = atan(dir.x, -dir.z);
+    float lat = acos(clamp(dir.y, -1.0, 1.0));
+    return vec2(lon / (2 * CUBE_PI) + 0.5, lat / CUBE_PI);
+}
+";
+
+const EQUIRECT_COMPUTE_SHADER: &str = "
+layout(local_size_x = 8, local_size_y = 8) in;
+layout(rgba32f, binding = 0) uniform writeonly imageCube dst;
+uniform sampler2D src;
+
+void main()
+{
+    ivec2 face_size = imageSize(dst);
+    ivec3 id = ivec3(gl_GlobalInvocationID);
+    if (id.x >= face_size.x || id.y >= face_size.y) {
+        return;
+    }
+    vec2 uv = (vec2(id.xy) + 0.5) / vec2(face_size) * 2 - 1;
+    vec2 coord = equirect_coord(face_direction(id.z, uv));
+    imageStore(dst, id, textureLod(src, coord, 0));
+}
+";
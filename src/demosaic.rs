@@ -0,0 +1,317 @@
+use crate::{
+    check_gl, create_compute_program, set_arg_u32,
+    texture::{CpuTexture, Texture},
+    Error,
+};
+use gl::types::*;
+
+const RED: usize = 0;
+const GREEN: usize = 1;
+const BLUE: usize = 2;
+
+// Named by the colors of the top-left 2x2 block, row by row
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BayerPattern {
+    Rggb,
+    Bggr,
+    Grbg,
+    Gbrg,
+}
+
+impl BayerPattern {
+    fn colors(self) -> [usize; 4] {
+        match self {
+            BayerPattern::Rggb => [RED, GREEN, GREEN, BLUE],
+            BayerPattern::Bggr => [BLUE, GREEN, GREEN, RED],
+            BayerPattern::Grbg => [GREEN, RED, BLUE, GREEN],
+            BayerPattern::Gbrg => [GREEN, BLUE, RED, GREEN],
+        }
+    }
+
+    pub fn color_at(self, x: usize, y: usize) -> usize {
+        self.colors()[(y % 2) * 2 + x % 2]
+    }
+
+    // two bits per position, as expected by the compute shader
+    fn packed(self) -> u32 {
+        let colors = self.colors();
+        (colors[0] | colors[1] << 2 | colors[2] << 4 | colors[3] << 6) as u32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DemosaicAlgorithm {
+    Bilinear,
+    // Malvar, He, Cutler: "High-quality linear interpolation for demosaicing of Bayer-patterned
+    // color images", bilinear plus gradient correction from a 5x5 neighbourhood
+    Malvar,
+}
+
+// Sparse kernels of (dx, dy, weight), named by what they interpolate:
+// G at R/B, R/B at G with the color in the same row or column, and R at B/B at R.
+struct Kernels {
+    g_at_rb: &'static [(isize, isize, f32)],
+    same_row: &'static [(isize, isize, f32)],
+    same_col: &'static [(isize, isize, f32)],
+    rb_at_br: &'static [(isize, isize, f32)],
+}
+
+const BILINEAR: Kernels = Kernels {
+    g_at_rb: &[(-1, 0, 0.25), (1, 0, 0.25), (0, -1, 0.25), (0, 1, 0.25)],
+    same_row: &[(-1, 0, 0.5), (1, 0, 0.5)],
+    same_col: &[(0, -1, 0.5), (0, 1, 0.5)],
+    rb_at_br: &[(-1, -1, 0.25), (1, -1, 0.25), (-1, 1, 0.25), (1, 1, 0.25)],
+};
+
+// weights are multiplied by 8
+const MALVAR: Kernels = Kernels {
+    g_at_rb: &[
+        (0, 0, 4.0),
+        (-1, 0, 2.0),
+        (1, 0, 2.0),
+        (0, -1, 2.0),
+        (0, 1, 2.0),
+        (-2, 0, -1.0),
+        (2, 0, -1.0),
+        (0, -2, -1.0),
+        (0, 2, -1.0),
+    ],
+    same_row: &[
+        (0, 0, 5.0),
+        (-1, 0, 4.0),
+        (1, 0, 4.0),
+        (-2, 0, -1.0),
+        (2, 0, -1.0),
+        (-1, -1, -1.0),
+        (1, -1, -1.0),
+        (-1, 1, -1.0),
+        (1, 1, -1.0),
+        (0, -2, 0.5),
+        (0, 2, 0.5),
+    ],
+    same_col: &[
+        (0, 0, 5.0),
+        (0, -1, 4.0),
+        (0, 1, 4.0),
+        (0, -2, -1.0),
+        (0, 2, -1.0),
+        (-1, -1, -1.0),
+        (1, -1, -1.0),
+        (-1, 1, -1.0),
+        (1, 1, -1.0),
+        (-2, 0, 0.5),
+        (2, 0, 0.5),
+    ],
+    rb_at_br: &[
+        (0, 0, 6.0),
+        (-1, -1, 2.0),
+        (1, -1, 2.0),
+        (-1, 1, 2.0),
+        (1, 1, 2.0),
+        (-2, 0, -1.5),
+        (2, 0, -1.5),
+        (0, -2, -1.5),
+        (0, 2, -1.5),
+    ],
+};
+
+fn apply(
+    raw: &CpuTexture<u16>,
+    pos: (usize, usize),
+    kernel: &[(isize, isize, f32)],
+    scale: f32,
+) -> f32 {
+    let mut sum = 0.0;
+    for &(dx, dy, weight) in kernel {
+        // mirroring keeps the bayer phase of the neighbours intact at the edges
+        let value = *raw.get_mirrored(pos.0 as isize + dx, pos.1 as isize + dy);
+        sum += value as f32 * weight;
+    }
+    (sum * scale / u16::MAX as f32).clamp(0.0, 1.0)
+}
+
+// Values are normalized to [0, 1] like sampling an R16 texture, alpha is 1
+pub fn demosaic(
+    raw: &CpuTexture<u16>,
+    pattern: BayerPattern,
+    algorithm: DemosaicAlgorithm,
+) -> CpuTexture<[f32; 4]> {
+    let (kernels, scale) = match algorithm {
+        DemosaicAlgorithm::Bilinear => (&BILINEAR, 1.0),
+        DemosaicAlgorithm::Malvar => (&MALVAR, 1.0 / 8.0),
+    };
+    let mut pixels = Vec::with_capacity(raw.size.0 * raw.size.1);
+    for (x, y) in raw.iter_index() {
+        let mut rgba = [0.0, 0.0, 0.0, 1.0];
+        let color = pattern.color_at(x, y);
+        rgba[color] = raw[(x, y)] as f32 / u16::MAX as f32;
+        if color == GREEN {
+            let row_color = pattern.color_at(x + 1, y);
+            let col_color = pattern.color_at(x, y + 1);
+            rgba[row_color] = apply(raw, (x, y), kernels.same_row, scale);
+            rgba[col_color] = apply(raw, (x, y), kernels.same_col, scale);
+        } else {
+            rgba[GREEN] = apply(raw, (x, y), kernels.g_at_rb, scale);
+            rgba[BLUE - color] = apply(raw, (x, y), kernels.rb_at_br, scale);
+        }
+        pixels.push(rgba);
+    }
+    CpuTexture::new(pixels, raw.size)
+}
+
+pub struct Demosaicer {
+    program: GLuint,
+}
+
+impl Demosaicer {
+    pub fn new() -> Result<Self, Error> {
+        let program = create_compute_program(&[DEMOSAIC_COMPUTE_SHADER])?;
+        if !program.success {
+            panic!("Failed to compile shader: {}", program.log);
+        }
+        Ok(Self {
+            program: program.shader,
+        })
+    }
+
+    pub fn demosaic(
+        &self,
+        raw: &Texture<u16>,
+        dst: &mut Texture<[f32; 4]>,
+        pattern: BayerPattern,
+        algorithm: DemosaicAlgorithm,
+    ) -> Result<(), Error> {
+        if raw.size != dst.size {
+            return Err(format!(
+                "Demosaic size mismatch: raw {:?}, dst {:?}",
+                raw.size, dst.size
+            )
+            .into());
+        }
+        set_arg_u32(self.program, "pattern", pattern.packed())?;
+        let malvar = (algorithm == DemosaicAlgorithm::Malvar) as u32;
+        set_arg_u32(self.program, "malvar", malvar)?;
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::BindTextureUnit(0, raw.id);
+            check_gl()?;
+            dst.bind(0)?;
+            gl::DispatchCompute(
+                (raw.size.0 as GLuint).div_ceil(8),
+                (raw.size.1 as GLuint).div_ceil(8),
+                1,
+            );
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT | gl::TEXTURE_UPDATE_BARRIER_BIT);
+            gl::BindTextureUnit(0, 0);
+            gl::UseProgram(0);
+            check_gl()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Demosaicer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+        }
+    }
+}
+
+const DEMOSAIC_COMPUTE_SHADER: &str = "
+#version 450
+
+layout(local_size_x = 8, local_size_y = 8) in;
+layout(rgba32f, binding = 0) uniform writeonly image2D dst;
+uniform sampler2D raw;
+uniform uint pattern;
+uniform uint malvar;
+
+ivec2 size;
+
+int mirror(int v, int len)
+{
+    if (len == 1) {
+        return 0;
+    }
+    int period = 2 * (len - 1);
+    v = ((v % period) + period) % period;
+    return v >= len ? period - v : v;
+}
+
+float at(ivec2 pos, int dx, int dy)
+{
+    ivec2 p = ivec2(mirror(pos.x + dx, size.x), mirror(pos.y + dy, size.y));
+    return texelFetch(raw, p, 0).r;
+}
+
+uint color_at(ivec2 pos)
+{
+    uint index = uint((pos.y & 1) * 2 + (pos.x & 1));
+    return (pattern >> (index * 2)) & 3;
+}
+
+float g_at_rb(ivec2 p)
+{
+    float adjacent = at(p, -1, 0) + at(p, 1, 0) + at(p, 0, -1) + at(p, 0, 1);
+    if (malvar == 0) {
+        return adjacent * 0.25;
+    }
+    float far = at(p, -2, 0) + at(p, 2, 0) + at(p, 0, -2) + at(p, 0, 2);
+    return (4 * at(p, 0, 0) + 2 * adjacent - far) / 8;
+}
+
+float same_row(ivec2 p)
+{
+    float row = at(p, -1, 0) + at(p, 1, 0);
+    if (malvar == 0) {
+        return row * 0.5;
+    }
+    float diag = at(p, -1, -1) + at(p, 1, -1) + at(p, -1, 1) + at(p, 1, 1);
+    float far_row = at(p, -2, 0) + at(p, 2, 0);
+    float far_col = at(p, 0, -2) + at(p, 0, 2);
+    return (5 * at(p, 0, 0) + 4 * row - far_row - diag + 0.5 * far_col) / 8;
+}
+
+float same_col(ivec2 p)
+{
+    float col = at(p, 0, -1) + at(p, 0, 1);
+    if (malvar == 0) {
+        return col * 0.5;
+    }
+    float diag = at(p, -1, -1) + at(p, 1, -1) + at(p, -1, 1) + at(p, 1, 1);
+    float far_row = at(p, -2, 0) + at(p, 2, 0);
+    float far_col = at(p, 0, -2) + at(p, 0, 2);
+    return (5 * at(p, 0, 0) + 4 * col - far_col - diag + 0.5 * far_row) / 8;
+}
+
+float rb_at_br(ivec2 p)
+{
+    float diag = at(p, -1, -1) + at(p, 1, -1) + at(p, -1, 1) + at(p, 1, 1);
+    if (malvar == 0) {
+        return diag * 0.25;
+    }
+    float far = at(p, -2, 0) + at(p, 2, 0) + at(p, 0, -2) + at(p, 0, 2);
+    return (6 * at(p, 0, 0) + 2 * diag - 1.5 * far) / 8;
+}
+
+void main()
+{
+    size = textureSize(raw, 0);
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    if (pos.x >= size.x || pos.y >= size.y) {
+        return;
+    }
+    vec4 rgba = vec4(0, 0, 0, 1);
+    uint color = color_at(pos);
+    rgba[color] = at(pos, 0, 0);
+    if (color == 1) {
+        rgba[color_at(pos + ivec2(1, 0))] = same_row(pos);
+        rgba[color_at(pos + ivec2(0, 1))] = same_col(pos);
+    } else {
+        rgba[1] = g_at_rb(pos);
+        rgba[2 - color] = rb_at_br(pos);
+    }
+    imageStore(dst, pos, clamp(rgba, 0, 1));
+}
+";
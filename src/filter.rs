@@ -0,0 +1,269 @@
+use crate::{
+    convert::{saturate, PixelConvert},
+    texture::{BorderMode, CpuTexture},
+};
+
+// Filters accumulate the raw channel values of PixelConvert in f64, so 32-bit integers pass
+// through unchanged, and round and saturate the result back to the pixel type.
+
+fn add_scaled(accum: &mut [f64; 4], value: [f64; 4], weight: f64) {
+    for (a, v) in accum.iter_mut().zip(&value) {
+        *a += v * weight;
+    }
+}
+
+fn accum_border<T: PixelConvert>(border: &BorderMode<T>) -> BorderMode<[f64; 4]> {
+    match border {
+        BorderMode::Clamp => BorderMode::Clamp,
+        BorderMode::Wrap => BorderMode::Wrap,
+        BorderMode::Mirror => BorderMode::Mirror,
+        BorderMode::Constant(value) => BorderMode::Constant(value.to_channels()),
+    }
+}
+
+fn gaussian_weights(sigma: f32) -> Vec<f64> {
+    if sigma <= 0.0 {
+        return vec![1.0];
+    }
+    let sigma = sigma as f64;
+    let radius = (sigma * 3.0).ceil().max(1.0) as isize;
+    let kernel = (-radius..=radius)
+        .map(|x| (-(x * x) as f64 / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<_>>();
+    let sum = kernel.iter().sum::<f64>();
+    kernel.into_iter().map(|w| w / sum).collect()
+}
+
+// sigma <= 0 gives the identity kernel [1.0]
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    gaussian_weights(sigma)
+        .into_iter()
+        .map(|w| w as f32)
+        .collect()
+}
+
+// Horizontal pass, then vertical pass, both centered at len / 2
+fn separable(
+    src: &CpuTexture<[f64; 4]>,
+    horizontal: &[f64],
+    vertical: &[f64],
+    border: &BorderMode<[f64; 4]>,
+) -> CpuTexture<[f64; 4]> {
+    let pass = |src: &CpuTexture<[f64; 4]>, kernel: &[f64], delta: (isize, isize)| {
+        let center = (kernel.len() / 2) as isize;
+        CpuTexture::from_fn(src.size, |(x, y)| {
+            let mut accum = [0.0; 4];
+            for (i, &weight) in kernel.iter().enumerate() {
+                let offset = i as isize - center;
+                let sx = x as isize + offset * delta.0;
+                let sy = y as isize + offset * delta.1;
+                add_scaled(&mut accum, *src.get_border(sx, sy, border), weight);
+            }
+            accum
+        })
+    };
+    let tmp = pass(src, horizontal, (1, 0));
+    pass(&tmp, vertical, (0, 1))
+}
+
+impl<T: PixelConvert> CpuTexture<T> {
+    fn to_accum_texture(&self) -> CpuTexture<[f64; 4]> {
+        self.map(T::to_channels)
+    }
+
+    fn separable_saturate(
+        &self,
+        horizontal: &[f64],
+        vertical: &[f64],
+        border: &BorderMode<T>,
+    ) -> Self {
+        let src = self.to_accum_texture();
+        separable(&src, horizontal, vertical, &accum_border(border)).map(|&accum| saturate(accum))
+    }
+
+    // kernel is centered at (width / 2, height / 2), and applied as correlation (not flipped)
+    pub fn convolve(&self, kernel: &CpuTexture<f32>, border: &BorderMode<T>) -> Self {
+        let center = ((kernel.size.0 / 2) as isize, (kernel.size.1 / 2) as isize);
+        CpuTexture::from_fn(self.size, |(x, y)| {
+            let mut accum = [0.0; 4];
+            for (kx, ky) in kernel.iter_index() {
+                let weight = kernel[(kx, ky)] as f64;
+                let sx = x as isize + kx as isize - center.0;
+                let sy = y as isize + ky as isize - center.1;
+                add_scaled(
+                    &mut accum,
+                    self.get_border(sx, sy, border).to_channels(),
+                    weight,
+                );
+            }
+            saturate(accum)
+        })
+    }
+
+    // Horizontal pass, then vertical pass, both centered at len / 2. The intermediate result is
+    // kept in f64 to avoid rounding twice.
+    pub fn convolve_separable(
+        &self,
+        horizontal: &[f32],
+        vertical: &[f32],
+        border: &BorderMode<T>,
+    ) -> Self {
+        let widen = |kernel: &[f32]| kernel.iter().map(|&w| w as f64).collect::<Vec<_>>();
+        self.separable_saturate(&widen(horizontal), &widen(vertical), border)
+    }
+
+    pub fn box_blur(&self, radius: usize, border: &BorderMode<T>) -> Self {
+        let kernel = vec![1.0 / (radius * 2 + 1) as f64; radius * 2 + 1];
+        self.separable_saturate(&kernel, &kernel, border)
+    }
+
+    pub fn gaussian_blur(&self, sigma: f32, border: &BorderMode<T>) -> Self {
+        let kernel = gaussian_weights(sigma);
+        self.separable_saturate(&kernel, &kernel, border)
+    }
+
+    // Per-channel median of the (2 * radius + 1)^2 neighbourhood
+    pub fn median(&self, radius: usize, border: &BorderMode<T>) -> Self {
+        let radius = radius as isize;
+        let mut values = vec![Vec::new(); T::CHANNELS];
+        CpuTexture::from_fn(self.size, |(x, y)| {
+            for channel in &mut values {
+                channel.clear();
+            }
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let pixel = self.get_border(x as isize + dx, y as isize + dy, border);
+                    let channels = pixel.to_channels();
+                    for (channel, &value) in values.iter_mut().zip(&channels) {
+                        channel.push(value);
+                    }
+                }
+            }
+            let mut result = [0.0; 4];
+            for (out, channel) in result.iter_mut().zip(&mut values) {
+                let mid = channel.len() / 2;
+                *out = *channel.select_nth_unstable_by(mid, |a, b| a.total_cmp(b)).1;
+            }
+            saturate(result)
+        })
+    }
+
+    fn sobel_accum(&self, border: &BorderMode<T>) -> (CpuTexture<[f64; 4]>, CpuTexture<[f64; 4]>) {
+        let src = self.to_accum_texture();
+        let border = accum_border(border);
+        let smooth = [1.0, 2.0, 1.0];
+        let derivative = [-1.0, 0.0, 1.0];
+        let gx = separable(&src, &derivative, &smooth, &border);
+        let gy = separable(&src, &smooth, &derivative, &border);
+        (gx, gy)
+    }
+
+    // Per-channel (d/dx, d/dy) with the 3x3 Sobel operator, unnormalized
+    pub fn sobel(&self, border: &BorderMode<T>) -> (CpuTexture<[f32; 4]>, CpuTexture<[f32; 4]>) {
+        let (gx, gy) = self.sobel_accum(border);
+        let narrow = |g: &[f64; 4]| [g[0] as f32, g[1] as f32, g[2] as f32, g[3] as f32];
+        (gx.map(narrow), gy.map(narrow))
+    }
+
+    pub fn sobel_magnitude(&self, border: &BorderMode<T>) -> Self {
+        let (gx, gy) = self.sobel_accum(border);
+        gx.zip_map(&gy, |gx, gy| {
+            let mut magnitude = [0.0; 4];
+            for (i, m) in magnitude.iter_mut().enumerate() {
+                *m = (gx[i] * gx[i] + gy[i] * gy[i]).sqrt();
+            }
+            saturate(magnitude)
+        })
+    }
+
+    // self + amount * (self - gaussian_blur(sigma))
+    pub fn unsharp_mask(&self, sigma: f32, amount: f32, border: &BorderMode<T>) -> Self {
+        let kernel = gaussian_weights(sigma);
+        let src = self.to_accum_texture();
+        let blurred = separable(&src, &kernel, &kernel, &accum_border(border));
+        src.zip_map(&blurred, |src, blurred| {
+            let mut result = *src;
+            for (r, b) in result.iter_mut().zip(blurred) {
+                *r += amount as f64 * (*r - b);
+            }
+            saturate(result)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaussian_kernel_normalized() {
+        for &sigma in &[0.3, 1.0, 2.5] {
+            let kernel = gaussian_kernel(sigma);
+            assert_eq!(kernel.len() % 2, 1);
+            assert!((kernel.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+            assert!(kernel.iter().eq(kernel.iter().rev()));
+            let mid = kernel.len() / 2;
+            assert!(kernel.iter().all(|&w| w <= kernel[mid]));
+        }
+        assert_eq!(gaussian_kernel(0.0), vec![1.0]);
+        assert_eq!(gaussian_kernel(-1.0), vec![1.0]);
+    }
+
+    #[test]
+    fn blur_keeps_constant_image() {
+        let texture = CpuTexture::new_val(200u16, (6, 5));
+        assert_eq!(
+            texture.box_blur(2, &BorderMode::Clamp).data(),
+            texture.data()
+        );
+        assert_eq!(
+            texture.gaussian_blur(1.5, &BorderMode::Mirror).data(),
+            texture.data()
+        );
+        assert_eq!(
+            texture.gaussian_blur(0.0, &BorderMode::Wrap).data(),
+            texture.data()
+        );
+    }
+
+    #[test]
+    fn convolve_border() {
+        let texture = CpuTexture::new(vec![3.0f32, 6.0, 9.0], (3, 1));
+        let kernel = CpuTexture::new(vec![0.0, 0.0, 1.0], (3, 1));
+        let shifted = texture.convolve(&kernel, &BorderMode::Constant(-1.0));
+        assert_eq!(shifted.data(), &[6.0, 9.0, -1.0]);
+        let shifted = texture.convolve(&kernel, &BorderMode::Wrap);
+        assert_eq!(shifted.data(), &[6.0, 9.0, 3.0]);
+    }
+
+    #[test]
+    fn median_removes_outlier() {
+        let mut texture = CpuTexture::new_val([10u8, 20, 30, 255], (3, 3));
+        texture[(1, 1)] = [255, 0, 255, 255];
+        let filtered = texture.median(1, &BorderMode::Clamp);
+        assert!(filtered.data().iter().all(|&p| p == [10, 20, 30, 255]));
+    }
+
+    #[test]
+    fn sobel_of_ramp() {
+        let texture = CpuTexture::from_fn((4, 4), |(x, _)| x as f32);
+        let (gx, gy) = texture.sobel(&BorderMode::Clamp);
+        // interior pixels see a slope of 1, scaled by 2 * (1 + 2 + 1)
+        assert_eq!(gx[(1, 1)][0], 8.0);
+        assert_eq!(gy[(1, 1)][0], 0.0);
+    }
+
+    #[test]
+    fn large_integers_are_exact() {
+        let texture = CpuTexture::new(vec![4_000_000_001u32, 16_777_217, 3, u32::MAX], (2, 2));
+        let identity = CpuTexture::new(vec![1.0], (1, 1));
+        let border = BorderMode::Clamp;
+        assert_eq!(texture.convolve(&identity, &border).data(), texture.data());
+        assert_eq!(texture.box_blur(0, &border).data(), texture.data());
+        assert_eq!(texture.gaussian_blur(0.0, &border).data(), texture.data());
+        assert_eq!(texture.median(0, &border).data(), texture.data());
+        let texture = CpuTexture::new(vec![i32::MIN + 1, 16_777_217, -16_777_217], (3, 1));
+        let blurred = texture.convolve_separable(&[1.0], &[1.0], &BorderMode::Wrap);
+        assert_eq!(blurred.data(), texture.data());
+    }
+}
@@ -0,0 +1,561 @@
+use crate::{
+    texture::{read_image_bytes, CpuTexture},
+    Error,
+};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{prelude::*, BufReader, BufWriter},
+    path::Path,
+};
+
+const BLOCK_SIZE: usize = 2880;
+const CARD_SIZE: usize = 80;
+
+// Keywords describing the data layout, written by write_fits itself (along with NAXISn)
+const STRUCTURAL_KEYWORDS: &[&str] = &[
+    "SIMPLE", "BITPIX", "EXTEND", "BZERO", "BSCALE", "BLANK", "END",
+];
+
+#[derive(Clone, Debug)]
+pub struct FitsCard {
+    pub keyword: String,
+    // Raw value text, e.g. "'a string'", "T", "1.5E3". None for commentary cards.
+    pub value: Option<String>,
+    pub comment: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FitsHeader {
+    pub cards: Vec<FitsCard>,
+}
+
+impl FitsHeader {
+    pub fn get(&self, keyword: &str) -> Option<&str> {
+        self.cards
+            .iter()
+            .find(|card| card.keyword == keyword)
+            .and_then(|card| card.value.as_deref())
+    }
+
+    pub fn get_str(&self, keyword: &str) -> Option<String> {
+        let value = self.get(keyword)?;
+        if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+            Some(
+                value[1..value.len() - 1]
+                    .replace("''", "'")
+                    .trim_end()
+                    .to_string(),
+            )
+        } else {
+            None
+        }
+    }
+
+    pub fn get_f64(&self, keyword: &str) -> Option<f64> {
+        self.get(keyword)?.replace('D', "E").parse().ok()
+    }
+
+    pub fn get_i64(&self, keyword: &str) -> Option<i64> {
+        self.get(keyword)?.parse().ok()
+    }
+
+    pub fn get_bool(&self, keyword: &str) -> Option<bool> {
+        match self.get(keyword)? {
+            "T" => Some(true),
+            "F" => Some(false),
+            _ => None,
+        }
+    }
+
+    // Replaces an existing card with the same keyword, or appends a new one
+    pub fn set(&mut self, keyword: &str, value: String, comment: &str) {
+        let card = FitsCard {
+            keyword: keyword.to_string(),
+            value: Some(value),
+            comment: comment.to_string(),
+        };
+        match self.cards.iter_mut().find(|c| c.keyword == keyword) {
+            Some(existing) => *existing = card,
+            None => self.cards.push(card),
+        }
+    }
+
+    pub fn set_str(&mut self, keyword: &str, value: &str, comment: &str) {
+        // fixed-format strings are at least 8 characters between the quotes
+        let value = format!("'{:<8}'", value.replace('\'', "''"));
+        self.set(keyword, value, comment)
+    }
+
+    pub fn set_f64(&mut self, keyword: &str, value: f64, comment: &str) {
+        self.set(keyword, format!("{:E}", value), comment)
+    }
+
+    pub fn set_i64(&mut self, keyword: &str, value: i64, comment: &str) {
+        self.set(keyword, value.to_string(), comment)
+    }
+
+    pub fn set_bool(&mut self, keyword: &str, value: bool, comment: &str) {
+        self.set(keyword, if value { "T" } else { "F" }.to_string(), comment)
+    }
+}
+
+fn parse_card(card: &[u8]) -> FitsCard {
+    let card = card
+        .iter()
+        .map(|&b| if b.is_ascii() { b as char } else { '?' })
+        .collect::<String>();
+    let keyword = card[..8].trim_end().to_string();
+    if &card[8..10] != "= " {
+        return FitsCard {
+            keyword,
+            value: None,
+            comment: card[8..].trim_end().to_string(),
+        };
+    }
+    let rest = &card[10..];
+    // the comment starts at the first '/' outside of a quoted string
+    let mut in_string = false;
+    let mut split = rest.len();
+    for (i, ch) in rest.char_indices() {
+        match ch {
+            '\'' => in_string = !in_string,
+            '/' if !in_string => {
+                split = i;
+                break;
+            }
+            _ => (),
+        }
+    }
+    let comment = rest[split..].trim_start_matches('/').trim().to_string();
+    FitsCard {
+        keyword,
+        value: Some(rest[..split].trim().to_string()),
+        comment,
+    }
+}
+
+// Long comments are truncated, but keywords and values have to fit in the card, as cutting them
+// off would produce an invalid header (e.g. a string without its closing quote)
+fn format_card(card: &FitsCard) -> Result<String, Error> {
+    let mut text = match &card.value {
+        Some(value) => format!("{:<8}= {:>20}", card.keyword, value),
+        None => format!("{:<8}", card.keyword),
+    };
+    if card.keyword.len() > 8 || text.len() > CARD_SIZE || !text.is_ascii() {
+        return Err(format!(
+            "FITS card does not fit in {} characters: {}",
+            CARD_SIZE, text
+        )
+        .into());
+    }
+    if card.value.is_some() && !card.comment.is_empty() && text.len() + 3 < CARD_SIZE {
+        text.push_str(" / ");
+    }
+    text.extend(
+        card.comment
+            .chars()
+            .map(|ch| if ch.is_ascii() { ch } else { '?' })
+            .take(CARD_SIZE.saturating_sub(text.len())),
+    );
+    while text.len() < CARD_SIZE {
+        text.push(' ');
+    }
+    Ok(text)
+}
+
+// Pixel types that FITS data can be converted to and from. Reading applies BZERO/BSCALE and
+// converts the physical value with from_physical, writing uses BITPIX/BZERO of the type.
+pub trait FitsPixel: Clone {
+    const BITPIX: i64;
+    const BZERO: f64;
+    fn from_physical(value: f64) -> Self;
+    // raw big-endian sample, before BZERO is subtracted
+    fn write_fits_bytes(&self, out: &mut Vec<u8>);
+}
+
+impl FitsPixel for u8 {
+    const BITPIX: i64 = 8;
+    const BZERO: f64 = 0.0;
+    fn from_physical(value: f64) -> Self {
+        value.round().clamp(0.0, u8::MAX as f64) as u8
+    }
+    fn write_fits_bytes(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+
+impl FitsPixel for u16 {
+    // the standard convention for unsigned 16-bit data: signed storage offset by 32768
+    const BITPIX: i64 = 16;
+    const BZERO: f64 = 32768.0;
+    fn from_physical(value: f64) -> Self {
+        value.round().clamp(0.0, u16::MAX as f64) as u16
+    }
+    fn write_fits_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&((*self as i32 - 32768) as i16).to_be_bytes());
+    }
+}
+
+impl FitsPixel for u32 {
+    const BITPIX: i64 = 32;
+    const BZERO: f64 = 2147483648.0;
+    fn from_physical(value: f64) -> Self {
+        value.round().clamp(0.0, u32::MAX as f64) as u32
+    }
+    fn write_fits_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&((*self as i64 - 2147483648) as i32).to_be_bytes());
+    }
+}
+
+impl FitsPixel for i32 {
+    const BITPIX: i64 = 32;
+    const BZERO: f64 = 0.0;
+    fn from_physical(value: f64) -> Self {
+        value.round().clamp(i32::MIN as f64, i32::MAX as f64) as i32
+    }
+    fn write_fits_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl FitsPixel for f32 {
+    const BITPIX: i64 = -32;
+    const BZERO: f64 = 0.0;
+    fn from_physical(value: f64) -> Self {
+        value as f32
+    }
+    fn write_fits_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl FitsPixel for f64 {
+    const BITPIX: i64 = -64;
+    const BZERO: f64 = 0.0;
+    fn from_physical(value: f64) -> Self {
+        value
+    }
+    fn write_fits_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+fn read_header(reader: &mut impl Read) -> Result<FitsHeader, Error> {
+    let mut header = FitsHeader::default();
+    let mut block = [0; BLOCK_SIZE];
+    loop {
+        reader.read_exact(&mut block)?;
+        for card in block.chunks_exact(CARD_SIZE) {
+            let card = parse_card(card);
+            if card.keyword == "END" {
+                return Ok(header);
+            }
+            if !card.keyword.is_empty() {
+                header.cards.push(card);
+            }
+        }
+    }
+}
+
+impl<T: FitsPixel> CpuTexture<T> {
+    // Reads the primary HDU, which must be a 2D image (further axes of length 1 are allowed).
+    // Rows are returned in file order, so y = 0 is the first row of the file, which FITS
+    // viewers conventionally display at the bottom. Integer samples equal to BLANK are read as
+    // NaN, so they are only accepted for floating point pixel types.
+    pub fn read_fits(reader: &mut impl Read) -> Result<(Self, FitsHeader), Error> {
+        let header = read_header(reader)?;
+        if header.get_bool("SIMPLE") != Some(true) {
+            return Err("Not a standard FITS file".into());
+        }
+        let bitpix = header.get_i64("BITPIX").ok_or("Missing BITPIX")?;
+        let naxis = header.get_i64("NAXIS").ok_or("Missing NAXIS")?;
+        if naxis < 2 {
+            return Err(format!("FITS primary HDU is not an image, NAXIS = {}", naxis).into());
+        }
+        let mut axes = Vec::new();
+        for axis in 1..=naxis {
+            let length = header
+                .get_i64(&format!("NAXIS{}", axis))
+                .ok_or_else(|| format!("Missing NAXIS{}", axis))?;
+            if length <= 0 {
+                return Err(format!("Invalid NAXIS{} = {}", axis, length).into());
+            }
+            axes.push(usize::try_from(length)?);
+        }
+        if axes[2..].iter().any(|&length| length != 1) {
+            return Err(format!("FITS image is not 2D, axes {:?}", axes).into());
+        }
+        let size = (axes[0], axes[1]);
+        let bzero = header.get_f64("BZERO").unwrap_or(0.0);
+        let bscale = header.get_f64("BSCALE").unwrap_or(1.0);
+        let bytes_per_sample = match bitpix {
+            8 => 1,
+            16 => 2,
+            32 | -32 => 4,
+            -64 => 8,
+            _ => return Err(format!("Unsupported BITPIX {}", bitpix).into()),
+        };
+        // BLANK is only defined for integer data, floating point data uses NaN
+        let blank = if bitpix > 0 {
+            header.get_i64("BLANK").map(|blank| blank as f64)
+        } else {
+            None
+        };
+        let bytes = read_image_bytes(reader, &[size.0, size.1, bytes_per_sample])?;
+        let pixels = bytes
+            .chunks_exact(bytes_per_sample)
+            .map(|b| {
+                let raw = match bitpix {
+                    8 => b[0] as f64,
+                    16 => i16::from_be_bytes([b[0], b[1]]) as f64,
+                    32 => i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    -32 => f32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]),
+                };
+                if Some(raw) != blank {
+                    Ok(T::from_physical(bzero + bscale * raw))
+                } else if T::BITPIX < 0 {
+                    Ok(T::from_physical(f64::NAN))
+                } else {
+                    Err(format!(
+                        "FITS data has BLANK pixels, which BITPIX {} cannot store",
+                        T::BITPIX
+                    ))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((CpuTexture::new(pixels, size), header))
+    }
+
+    // Writes a primary HDU. Structural keywords in extra are ignored, everything else is copied.
+    pub fn write_fits(&self, writer: &mut impl Write, extra: &FitsHeader) -> Result<(), Error> {
+        let mut header = FitsHeader::default();
+        header.set_bool("SIMPLE", true, "conforms to FITS standard");
+        header.set_i64("BITPIX", T::BITPIX, "");
+        header.set_i64("NAXIS", 2, "");
+        header.set_i64("NAXIS1", self.size.0 as i64, "");
+        header.set_i64("NAXIS2", self.size.1 as i64, "");
+        if T::BZERO != 0.0 {
+            header.set(
+                "BZERO",
+                format!("{}", T::BZERO),
+                "offset data range to unsigned",
+            );
+            header.set("BSCALE", "1".to_string(), "");
+        }
+        for card in &extra.cards {
+            let structural = STRUCTURAL_KEYWORDS.contains(&&card.keyword[..])
+                || card.keyword.starts_with("NAXIS");
+            if !structural {
+                header.cards.push(card.clone());
+            }
+        }
+        header.cards.push(FitsCard {
+            keyword: "END".to_string(),
+            value: None,
+            comment: String::new(),
+        });
+        let mut bytes = Vec::new();
+        for card in &header.cards {
+            bytes.extend_from_slice(format_card(card)?.as_bytes());
+        }
+        bytes.resize(bytes.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, b' ');
+        let header_len = bytes.len();
+        for pixel in self.data() {
+            pixel.write_fits_bytes(&mut bytes);
+        }
+        let data_len = bytes.len() - header_len;
+        bytes.resize(header_len + data_len.div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn load_fits(path: impl AsRef<Path>) -> Result<(Self, FitsHeader), Error> {
+        Self::read_fits(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save_fits(&self, path: impl AsRef<Path>, extra: &FitsHeader) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_fits(&mut writer, extra)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: FitsPixel>(
+        texture: &CpuTexture<T>,
+        extra: &FitsHeader,
+    ) -> (CpuTexture<T>, FitsHeader) {
+        let mut bytes = Vec::new();
+        texture.write_fits(&mut bytes, extra).unwrap();
+        assert_eq!(bytes.len() % BLOCK_SIZE, 0);
+        CpuTexture::read_fits(&mut &bytes[..]).unwrap()
+    }
+
+    fn header_bytes(cards: &[(&str, &str)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for &(keyword, value) in cards {
+            let card = FitsCard {
+                keyword: keyword.to_string(),
+                value: Some(value.to_string()),
+                comment: String::new(),
+            };
+            bytes.extend_from_slice(format_card(&card).unwrap().as_bytes());
+        }
+        bytes.extend_from_slice(format!("{:<80}", "END").as_bytes());
+        bytes.resize(BLOCK_SIZE, b' ');
+        bytes
+    }
+
+    #[test]
+    fn round_trip_u16() {
+        let texture = CpuTexture::new(vec![0u16, 1, 32767, 32768, 65535, 1234], (3, 2));
+        let (read, header) = round_trip(&texture, &FitsHeader::default());
+        assert_eq!(read.size, (3, 2));
+        assert_eq!(read.data(), texture.data());
+        assert_eq!(header.get_i64("BITPIX"), Some(16));
+        assert_eq!(header.get_f64("BZERO"), Some(32768.0));
+    }
+
+    #[test]
+    fn round_trip_u32_i32_f32() {
+        let texture = CpuTexture::new(vec![0u32, 1, u32::MAX, 1 << 31], (2, 2));
+        assert_eq!(
+            round_trip(&texture, &FitsHeader::default()).0.data(),
+            texture.data()
+        );
+        let texture = CpuTexture::new(vec![i32::MIN, -1, 0, i32::MAX], (4, 1));
+        assert_eq!(
+            round_trip(&texture, &FitsHeader::default()).0.data(),
+            texture.data()
+        );
+        let texture = CpuTexture::new(vec![-1.5f32, 0.0, 1e-20, 3.25e10], (1, 4));
+        assert_eq!(
+            round_trip(&texture, &FitsHeader::default()).0.data(),
+            texture.data()
+        );
+    }
+
+    #[test]
+    fn header_cards() {
+        let mut extra = FitsHeader::default();
+        extra.set_str("OBJECT", "M31 'core'", "target");
+        extra.set_f64("EXPTIME", 30.5, "seconds");
+        extra.set_bool("FLIPPED", false, "");
+        // structural keywords are written by write_fits itself
+        extra.set_i64("NAXIS1", 100, "");
+        let texture = CpuTexture::new(vec![1.0f32; 6], (2, 3));
+        let (_, header) = round_trip(&texture, &extra);
+        assert_eq!(header.get_str("OBJECT").as_deref(), Some("M31 'core'"));
+        assert_eq!(header.get_f64("EXPTIME"), Some(30.5));
+        assert_eq!(header.get_bool("FLIPPED"), Some(false));
+        assert_eq!(header.get_i64("NAXIS1"), Some(2));
+        let object = header
+            .cards
+            .iter()
+            .find(|card| card.keyword == "OBJECT")
+            .unwrap();
+        assert_eq!(object.comment, "target");
+    }
+
+    #[test]
+    fn extra_axes_of_length_one() {
+        let mut bytes = header_bytes(&[
+            ("SIMPLE", "T"),
+            ("BITPIX", "8"),
+            ("NAXIS", "3"),
+            ("NAXIS1", "2"),
+            ("NAXIS2", "1"),
+            ("NAXIS3", "1"),
+        ]);
+        bytes.extend_from_slice(&[10, 200]);
+        let (texture, _) = CpuTexture::<u16>::read_fits(&mut &bytes[..]).unwrap();
+        assert_eq!(texture.size, (2, 1));
+        assert_eq!(texture.data(), &[10, 200]);
+    }
+
+    #[test]
+    fn invalid_axes() {
+        for naxis1 in &["0", "-1"] {
+            let bytes = header_bytes(&[
+                ("SIMPLE", "T"),
+                ("BITPIX", "8"),
+                ("NAXIS", "2"),
+                ("NAXIS1", naxis1),
+                ("NAXIS2", "1"),
+            ]);
+            assert!(CpuTexture::<u16>::read_fits(&mut &bytes[..]).is_err());
+        }
+        let huge = i64::MAX.to_string();
+        let bytes = header_bytes(&[
+            ("SIMPLE", "T"),
+            ("BITPIX", "-64"),
+            ("NAXIS", "2"),
+            ("NAXIS1", &huge),
+            ("NAXIS2", &huge),
+        ]);
+        assert!(CpuTexture::<f32>::read_fits(&mut &bytes[..]).is_err());
+    }
+
+    #[test]
+    fn truncated_data() {
+        let texture = CpuTexture::new(vec![7u16; 4], (2, 2));
+        let mut bytes = Vec::new();
+        texture
+            .write_fits(&mut bytes, &FitsHeader::default())
+            .unwrap();
+        bytes.truncate(BLOCK_SIZE + 3);
+        assert!(CpuTexture::<u16>::read_fits(&mut &bytes[..]).is_err());
+    }
+
+    #[test]
+    fn round_trip_u8_f64() {
+        let texture = CpuTexture::new(vec![0u8, 7, 128, 255], (2, 2));
+        let (read, header) = round_trip(&texture, &FitsHeader::default());
+        assert_eq!(read.data(), texture.data());
+        assert_eq!(header.get_i64("BITPIX"), Some(8));
+        let texture = CpuTexture::new(vec![1e-300f64, -0.1, f64::MAX], (3, 1));
+        let (read, header) = round_trip(&texture, &FitsHeader::default());
+        assert_eq!(read.data(), texture.data());
+        assert_eq!(header.get_i64("BITPIX"), Some(-64));
+    }
+
+    #[test]
+    fn long_cards() {
+        let texture = CpuTexture::new(vec![0u16], (1, 1));
+        let mut extra = FitsHeader::default();
+        extra.set_str("OBJECT", &"x".repeat(60), &"long comment ".repeat(10));
+        let (_, header) = round_trip(&texture, &extra);
+        assert_eq!(header.get_str("OBJECT"), Some("x".repeat(60)));
+        // no room left for the comment
+        extra.set_str("OBJECT", &"x".repeat(68), "comment");
+        assert_eq!(
+            round_trip(&texture, &extra).1.get_str("OBJECT"),
+            Some("x".repeat(68))
+        );
+        extra.set_str("OBJECT", &"x".repeat(69), "");
+        assert!(texture.write_fits(&mut Vec::new(), &extra).is_err());
+    }
+
+    #[test]
+    fn blank_pixels() {
+        let mut bytes = header_bytes(&[
+            ("SIMPLE", "T"),
+            ("BITPIX", "16"),
+            ("NAXIS", "2"),
+            ("NAXIS1", "2"),
+            ("NAXIS2", "1"),
+            ("BLANK", "-1"),
+        ]);
+        bytes.extend_from_slice(&[0, 5, 0xff, 0xff]);
+        let (texture, _) = CpuTexture::<f32>::read_fits(&mut &bytes[..]).unwrap();
+        assert_eq!(texture[(0, 0)], 5.0);
+        assert!(texture[(1, 0)].is_nan());
+        assert!(CpuTexture::<u16>::read_fits(&mut &bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{
+    texture::{read_image_bytes, read_netpbm_token, CpuTexture},
+    Error,
+};
+use std::{
+    fs::File,
+    io::{prelude::*, BufReader, BufWriter},
+    path::Path,
+};
+
+// Pixel types that can be stored losslessly in floating point image formats
+pub trait FloatPixel: Clone + Send + Sync + 'static {
+    const CHANNELS: usize;
+    fn from_channels(channels: &[f32]) -> Self;
+    fn channels(&self) -> &[f32];
+}
+
+impl FloatPixel for f32 {
+    const CHANNELS: usize = 1;
+    fn from_channels(channels: &[f32]) -> Self {
+        channels[0]
+    }
+    fn channels(&self) -> &[f32] {
+        std::slice::from_ref(self)
+    }
+}
+
+impl FloatPixel for [f32; 4] {
+    const CHANNELS: usize = 4;
+    fn from_channels(channels: &[f32]) -> Self {
+        match *channels {
+            [v] => [v, v, v, 1.0],
+            [r, g, b] => [r, g, b, 1.0],
+            [r, g, b, a] => [r, g, b, a],
+            _ => panic!("Invalid channel count: {}", channels.len()),
+        }
+    }
+    fn channels(&self) -> &[f32] {
+        self
+    }
+}
+
+impl<T: FloatPixel> CpuTexture<T> {
+    // Portable float map: "PF" (rgb) or "Pf" (grayscale) header, rows stored bottom to top.
+    // Grayscale files are expanded to rgba and rgb files are rejected for single-channel pixels.
+    pub fn read_pfm(reader: &mut impl Read) -> Result<Self, Error> {
+        let channels = match &read_netpbm_token(reader)?[..] {
+            "PF" => 3,
+            "Pf" => 1,
+            magic => return Err(format!("Not a PFM file, magic: {:?}", magic).into()),
+        };
+        if channels > T::CHANNELS {
+            return Err(format!("Cannot load {} channel PFM into this type", channels).into());
+        }
+        let width = read_netpbm_token(reader)?.parse::<usize>()?;
+        let height = read_netpbm_token(reader)?.parse::<usize>()?;
+        let scale = read_netpbm_token(reader)?.parse::<f32>()?;
+        let little_endian = scale < 0.0;
+        let bytes = read_image_bytes(reader, &[width, height, channels, 4])?;
+        let values = bytes
+            .chunks_exact(4)
+            .map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                if little_endian {
+                    f32::from_le_bytes(b)
+                } else {
+                    f32::from_be_bytes(b)
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let index = (y * width + x) * channels;
+                pixels.push(T::from_channels(&values[index..index + channels]));
+            }
+        }
+        Ok(CpuTexture::new(pixels, (width, height)))
+    }
+
+    // PFM has no alpha channel, so rgba pixels with an alpha other than 1.0 are rejected rather
+    // than silently made opaque
+    pub fn write_pfm(&self, writer: &mut impl Write) -> Result<(), Error> {
+        let (magic, channels) = if T::CHANNELS == 1 {
+            ("Pf", 1)
+        } else {
+            ("PF", 3)
+        };
+        if let Some(alpha) = self
+            .data()
+            .iter()
+            .filter_map(|pixel| pixel.channels().get(3).copied())
+            .find(|&alpha| alpha != 1.0)
+        {
+            return Err(format!("PFM cannot store alpha, but a pixel has alpha {}", alpha).into());
+        }
+        let mut bytes = Vec::with_capacity(self.size.0 * self.size.1 * channels * 4);
+        for y in (0..self.size.1).rev() {
+            for x in 0..self.size.0 {
+                for value in &self[(x, y)].channels()[..channels] {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        write!(writer, "{}\n{} {}\n-1.0\n", magic, self.size.0, self.size.1)?;
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn load_pfm(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read_pfm(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save_pfm(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_pfm(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "exr")]
+impl<T: FloatPixel> CpuTexture<T> {
+    // Reads the first layer with either rgb(a) or Y channels, converting samples to f32
+    pub fn load_exr(path: impl AsRef<Path>) -> Result<Self, Error> {
+        use exr::prelude::*;
+        let create = |resolution: Vec2<usize>| {
+            CpuTexture::new_val(
+                T::from_channels(&[0.0; 4][..T::CHANNELS]),
+                (resolution.width(), resolution.height()),
+            )
+        };
+        let path = path.as_ref();
+        let image = if T::CHANNELS == 1 {
+            read()
+                .no_deep_data()
+                .largest_resolution_level()
+                .specific_channels()
+                .required("Y")
+                .collect_pixels(
+                    move |resolution, _| create(resolution),
+                    |texture: &mut CpuTexture<T>, position, (y,): (f32,)| {
+                        texture[(position.x(), position.y())] = T::from_channels(&[y])
+                    },
+                )
+                .first_valid_layer()
+                .all_attributes()
+                .from_file(path)?
+                .layer_data
+                .channel_data
+                .pixels
+        } else {
+            read_first_rgba_layer_from_file(
+                path,
+                move |resolution, _| create(resolution),
+                |texture: &mut CpuTexture<T>, position, (r, g, b, a): (f32, f32, f32, f32)| {
+                    texture[(position.x(), position.y())] = T::from_channels(&[r, g, b, a])
+                },
+            )?
+            .layer_data
+            .channel_data
+            .pixels
+        };
+        Ok(image)
+    }
+
+    // Writes full 32-bit float samples, as Y for single-channel pixels and RGBA otherwise
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        use exr::prelude::*;
+        let size = (self.size.0, self.size.1);
+        if T::CHANNELS == 1 {
+            let channels = SpecificChannels::build()
+                .with_channel("Y")
+                .with_pixel_fn(|position| (self[(position.x(), position.y())].channels()[0],));
+            Image::from_channels(size, channels).write().to_file(path)?;
+        } else {
+            write_rgba_file(path, size.0, size.1, |x, y| {
+                let channels = self[(x, y)].channels();
+                (channels[0], channels[1], channels[2], channels[3])
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: FloatPixel, U: FloatPixel>(texture: &CpuTexture<T>) -> CpuTexture<U> {
+        let mut bytes = Vec::new();
+        texture.write_pfm(&mut bytes).unwrap();
+        CpuTexture::read_pfm(&mut &bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn pfm_round_trip() {
+        let texture = CpuTexture::new(vec![-1.0f32, 0.5, 2.0, 1e-10, 3e20, 0.0], (3, 2));
+        let read = round_trip::<_, f32>(&texture);
+        assert_eq!(read.size, (3, 2));
+        assert_eq!(read.data(), texture.data());
+        // grayscale files expand to opaque rgba
+        let rgba = round_trip::<_, [f32; 4]>(&texture);
+        assert_eq!(rgba[(0, 1)], [1e-10, 1e-10, 1e-10, 1.0]);
+
+        let texture = CpuTexture::new(vec![[0.1, 0.2, 0.3, 1.0], [4.0, 5.0, 6.0, 1.0]], (1, 2));
+        assert_eq!(round_trip::<_, [f32; 4]>(&texture).data(), texture.data());
+        let mut bytes = Vec::new();
+        texture.write_pfm(&mut bytes).unwrap();
+        assert!(CpuTexture::<f32>::read_pfm(&mut &bytes[..]).is_err());
+    }
+
+    #[test]
+    fn pfm_rejects_alpha() {
+        let texture = CpuTexture::new(vec![[0.1, 0.2, 0.3, 1.0], [4.0, 5.0, 6.0, 0.5]], (2, 1));
+        assert!(texture.write_pfm(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn pfm_big_endian_and_invalid() {
+        let mut bytes = b"Pf\n2 1\n1.0\n".to_vec();
+        bytes.extend_from_slice(&1.5f32.to_be_bytes());
+        bytes.extend_from_slice(&(-2.0f32).to_be_bytes());
+        let read = CpuTexture::<f32>::read_pfm(&mut &bytes[..]).unwrap();
+        assert_eq!(read.data(), &[1.5, -2.0]);
+        // truncated data
+        assert!(CpuTexture::<f32>::read_pfm(&mut &bytes[..bytes.len() - 1]).is_err());
+        let header = format!("Pf\n{} {}\n-1.0\n", usize::MAX, usize::MAX);
+        assert!(CpuTexture::<f32>::read_pfm(&mut header.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn pfm_files() {
+        let path = std::env::temp_dir().join(format!("khygl-{}.pfm", std::process::id()));
+        let texture = CpuTexture::new(vec![0.25f32, -8.0], (1, 2));
+        texture.save_pfm(&path).unwrap();
+        let read = CpuTexture::<f32>::load_pfm(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap().data(), texture.data());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{
+    check_gl,
+    multisample::{MultisampleRenderbuffer, MultisampleTexture},
+    texture::{Texture, TextureType},
+    Error, Rect,
+};
+use gl::types::*;
+
+pub struct Framebuffer {
+    pub id: GLuint,
+}
+
+impl Framebuffer {
+    pub fn new() -> Result<Self, Error> {
+        let mut id = 0;
+        unsafe {
+            gl::CreateFramebuffers(1, &mut id);
+            check_gl()?;
+        }
+        Ok(Self { id })
+    }
+
+    pub fn with_texture<T: TextureType>(texture: &Texture<T>) -> Result<Self, Error> {
+        let framebuffer = Self::new()?;
+        framebuffer.attach_texture(0, texture)?;
+        framebuffer.check_complete()?;
+        Ok(framebuffer)
+    }
+
+    pub fn attach_texture<T: TextureType>(
+        &self,
+        color_attachment: usize,
+        texture: &Texture<T>,
+    ) -> Result<(), Error> {
+        self.attach_texture_id(color_attachment, texture.id)
+    }
+
+    pub fn attach_multisample_texture<T: TextureType>(
+        &self,
+        color_attachment: usize,
+        texture: &MultisampleTexture<T>,
+    ) -> Result<(), Error> {
+        self.attach_texture_id(color_attachment, texture.id)
+    }
+
+    fn attach_texture_id(&self, color_attachment: usize, texture: GLuint) -> Result<(), Error> {
+        unsafe {
+            gl::NamedFramebufferTexture(
+                self.id,
+                gl::COLOR_ATTACHMENT0 + color_attachment as GLenum,
+                texture,
+                0,
+            );
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    pub fn attach_renderbuffer<T: TextureType>(
+        &self,
+        color_attachment: usize,
+        renderbuffer: &MultisampleRenderbuffer<T>,
+    ) -> Result<(), Error> {
+        unsafe {
+            gl::NamedFramebufferRenderbuffer(
+                self.id,
+                gl::COLOR_ATTACHMENT0 + color_attachment as GLenum,
+                gl::RENDERBUFFER,
+                renderbuffer.id,
+            );
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    pub fn check_complete(&self) -> Result<(), Error> {
+        let status = unsafe { gl::CheckNamedFramebufferStatus(self.id, gl::FRAMEBUFFER) };
+        check_gl()?;
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(format!("Framebuffer incomplete: {}", status).into())
+        }
+    }
+
+    // Also sets the viewport, as every renderer in this crate draws to the whole viewport
+    pub fn bind(&self, size: (usize, usize)) -> Result<(), Error> {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, size.0 as GLsizei, size.1 as GLsizei);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    pub fn unbind(&self) -> Result<(), Error> {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    // filter: gl::NEAREST or gl::LINEAR. Multisample sources require gl::NEAREST and equal
+    // rect sizes.
+    pub fn blit(
+        &self,
+        dst: &Framebuffer,
+        src_rect: Rect<usize>,
+        dst_rect: Rect<usize>,
+        filter: GLenum,
+    ) -> Result<(), Error> {
+        unsafe {
+            gl::BlitNamedFramebuffer(
+                self.id,
+                dst.id,
+                src_rect.x as GLint,
+                src_rect.y as GLint,
+                src_rect.right() as GLint,
+                src_rect.bottom() as GLint,
+                dst_rect.x as GLint,
+                dst_rect.y as GLint,
+                dst_rect.right() as GLint,
+                dst_rect.bottom() as GLint,
+                gl::COLOR_BUFFER_BIT,
+                filter,
+            );
+            check_gl()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            check_gl().expect("Failed to delete framebuffer in drop impl");
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    texture::{BorderMode, CpuTexture},
+    Rect,
+};
+
+impl<T> CpuTexture<T> {
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> CpuTexture<U> {
+        CpuTexture::new(self.data().iter().map(f).collect(), self.size)
+    }
+
+    pub fn zip_map<U, V>(
+        &self,
+        other: &CpuTexture<U>,
+        mut f: impl FnMut(&T, &U) -> V,
+    ) -> CpuTexture<V> {
+        assert_eq!(self.size, other.size);
+        let pixels = self
+            .data()
+            .iter()
+            .zip(other.data())
+            .map(|(a, b)| f(a, b))
+            .collect();
+        CpuTexture::new(pixels, self.size)
+    }
+}
+
+impl<T: Clone> CpuTexture<T> {
+    pub fn crop(&self, rect: Rect<usize>) -> Self {
+        assert!(
+            rect.right() <= self.size.0 && rect.bottom() <= self.size.1,
+            "Crop {:?} out of range (size {:?})",
+            rect,
+            self.size
+        );
+        CpuTexture::from_fn((rect.width, rect.height), |(x, y)| {
+            self[(rect.x + x, rect.y + y)].clone()
+        })
+    }
+
+    pub fn flip_horizontal(&self) -> Self {
+        CpuTexture::from_fn(self.size, |(x, y)| self[(self.size.0 - 1 - x, y)].clone())
+    }
+
+    pub fn flip_vertical(&self) -> Self {
+        CpuTexture::from_fn(self.size, |(x, y)| self[(x, self.size.1 - 1 - y)].clone())
+    }
+
+    pub fn transpose(&self) -> Self {
+        CpuTexture::from_fn((self.size.1, self.size.0), |(x, y)| self[(y, x)].clone())
+    }
+
+    // clockwise, with y pointing down
+    pub fn rotate_90(&self) -> Self {
+        CpuTexture::from_fn((self.size.1, self.size.0), |(x, y)| {
+            self[(y, self.size.1 - 1 - x)].clone()
+        })
+    }
+
+    pub fn rotate_180(&self) -> Self {
+        CpuTexture::from_fn(self.size, |(x, y)| {
+            self[(self.size.0 - 1 - x, self.size.1 - 1 - y)].clone()
+        })
+    }
+
+    pub fn rotate_270(&self) -> Self {
+        CpuTexture::from_fn((self.size.1, self.size.0), |(x, y)| {
+            self[(self.size.0 - 1 - y, x)].clone()
+        })
+    }
+
+    // Adds left/top/right/bottom pixels around the image, filled according to border
+    pub fn pad(
+        &self,
+        left: usize,
+        top: usize,
+        right: usize,
+        bottom: usize,
+        border: &BorderMode<T>,
+    ) -> Self {
+        let size = (self.size.0 + left + right, self.size.1 + top + bottom);
+        CpuTexture::from_fn(size, |(x, y)| {
+            self.get_border(
+                x as isize - left as isize,
+                y as isize - top as isize,
+                border,
+            )
+            .clone()
+        })
+    }
+
+    // Copies src into self with its top-left corner at pos, clipping anything out of range
+    pub fn paste(&mut self, src: &CpuTexture<T>, pos: (isize, isize)) {
+        let x_start = (-pos.0).max(0) as usize;
+        let y_start = (-pos.1).max(0) as usize;
+        let x_end = (self.size.0 as isize - pos.0).clamp(0, src.size.0 as isize) as usize;
+        let y_end = (self.size.1 as isize - pos.1).clamp(0, src.size.1 as isize) as usize;
+        for y in y_start..y_end {
+            for x in x_start..x_end {
+                let dst = ((x as isize + pos.0) as usize, (y as isize + pos.1) as usize);
+                self[dst] = src[(x, y)].clone();
+            }
+        }
+    }
+}
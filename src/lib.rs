@@ -1,6 +1,29 @@
+pub mod block;
+pub mod buffer;
+pub mod colormap;
+pub mod convert;
+pub mod cube_texture;
+pub mod demosaic;
+pub mod filter;
+pub mod fits;
+pub mod float_io;
+pub mod framebuffer;
+pub mod image_ops;
+pub mod multisample;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+#[cfg(feature = "png")]
+pub mod png_io;
+pub mod render_cube;
 pub mod render_text;
 pub mod render_texture;
+pub mod resample;
+pub mod ring_buffer;
+pub mod statistics;
+pub mod stretch;
 pub mod texture;
+pub mod vertex;
+pub mod vertex_array;
 
 use gl::types::*;
 use std::{
@@ -140,6 +163,9 @@ pub fn set_arg_u32(kernel: GLuint, key: &str, value: u32) -> Result<(), Error> {
     Ok(())
 }
 
+// First source of every shader, as #version has to come before anything else
+pub(crate) const GLSL_VERSION: &str = "#version 450\n";
+
 pub struct CompileResult {
     pub shader: GLuint,
     pub success: bool,
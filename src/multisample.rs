@@ -0,0 +1,142 @@
+use crate::{
+    check_gl,
+    framebuffer::Framebuffer,
+    texture::{get_internal_format_info_target, Texture, TextureType},
+    Error, Rect,
+};
+use gl::types::*;
+use std::marker::PhantomData;
+
+// Sample counts usable with T for target (gl::TEXTURE_2D_MULTISAMPLE or gl::RENDERBUFFER), in
+// descending order.
+pub fn supported_sample_counts<T: TextureType>(target: GLenum) -> Result<Vec<usize>, Error> {
+    let count =
+        get_internal_format_info_target(target, T::internalformat(), gl::NUM_SAMPLE_COUNTS, 1)?[0];
+    if count <= 0 {
+        return Ok(Vec::new());
+    }
+    let samples =
+        get_internal_format_info_target(target, T::internalformat(), gl::SAMPLES, count as usize)?;
+    Ok(samples.into_iter().map(|s| s as usize).collect())
+}
+
+pub struct MultisampleTexture<T: TextureType> {
+    pub id: GLuint,
+    pub size: (usize, usize),
+    pub samples: usize,
+    _t: PhantomData<T>,
+}
+
+impl<T: TextureType> MultisampleTexture<T> {
+    pub fn new(size: (usize, usize), samples: usize) -> Result<Self, Error> {
+        let format = T::internalformat();
+        let mut texture = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_2D_MULTISAMPLE, 1, &mut texture);
+            check_gl()?;
+            gl::TextureStorage2DMultisample(
+                texture,
+                samples as _,
+                format,
+                size.0 as _,
+                size.1 as _,
+                gl::TRUE,
+            );
+            check_gl()?;
+        }
+        Ok(Self {
+            id: texture,
+            size,
+            samples,
+            _t: PhantomData,
+        })
+    }
+
+    pub fn framebuffer(&self) -> Result<Framebuffer, Error> {
+        let framebuffer = Framebuffer::new()?;
+        framebuffer.attach_multisample_texture(0, self)?;
+        framebuffer.check_complete()?;
+        Ok(framebuffer)
+    }
+
+    pub fn resolve(&self, dst: &mut Texture<T>) -> Result<(), Error> {
+        resolve(&self.framebuffer()?, self.size, dst)
+    }
+}
+
+impl<T: TextureType> Drop for MultisampleTexture<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+        check_gl().expect("Failed to delete multisample texture in drop impl");
+    }
+}
+
+pub struct MultisampleRenderbuffer<T: TextureType> {
+    pub id: GLuint,
+    pub size: (usize, usize),
+    pub samples: usize,
+    _t: PhantomData<T>,
+}
+
+impl<T: TextureType> MultisampleRenderbuffer<T> {
+    pub fn new(size: (usize, usize), samples: usize) -> Result<Self, Error> {
+        let mut renderbuffer = 0;
+        unsafe {
+            gl::CreateRenderbuffers(1, &mut renderbuffer);
+            check_gl()?;
+            gl::NamedRenderbufferStorageMultisample(
+                renderbuffer,
+                samples as _,
+                T::internalformat(),
+                size.0 as _,
+                size.1 as _,
+            );
+            check_gl()?;
+        }
+        Ok(Self {
+            id: renderbuffer,
+            size,
+            samples,
+            _t: PhantomData,
+        })
+    }
+
+    pub fn framebuffer(&self) -> Result<Framebuffer, Error> {
+        let framebuffer = Framebuffer::new()?;
+        framebuffer.attach_renderbuffer(0, self)?;
+        framebuffer.check_complete()?;
+        Ok(framebuffer)
+    }
+
+    pub fn resolve(&self, dst: &mut Texture<T>) -> Result<(), Error> {
+        resolve(&self.framebuffer()?, self.size, dst)
+    }
+}
+
+impl<T: TextureType> Drop for MultisampleRenderbuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.id);
+        }
+        check_gl().expect("Failed to delete renderbuffer in drop impl");
+    }
+}
+
+fn resolve<T: TextureType>(
+    src: &Framebuffer,
+    size: (usize, usize),
+    dst: &mut Texture<T>,
+) -> Result<(), Error> {
+    if size != dst.size {
+        return Err(format!(
+            "Cannot resolve multisample image of size {:?} into texture of size {:?}",
+            size, dst.size
+        )
+        .into());
+    }
+    let dst_framebuffer = Framebuffer::with_texture(dst)?;
+    let rect = Rect::new(0, 0, size.0, size.1);
+    src.blit(&dst_framebuffer, rect.clone(), rect, gl::NEAREST)
+}
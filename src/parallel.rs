@@ -0,0 +1,161 @@
+use crate::{
+    texture::{CpuTexture, CpuTextureViewMut},
+    Rect,
+};
+use rayon::prelude::*;
+
+impl<T: Send> CpuTexture<T> {
+    pub fn par_from_fn(
+        size: (usize, usize),
+        f: impl Fn((usize, usize)) -> T + Sync + Send,
+    ) -> Self {
+        let width = size.0.max(1);
+        let data = (0..size.0 * size.1)
+            .into_par_iter()
+            .map(|i| f((i % width, i / width)))
+            .collect();
+        Self::new(data, size)
+    }
+
+    pub fn par_rows_mut(&mut self) -> impl IndexedParallelIterator<Item = (usize, &mut [T])> {
+        let width = self.size.0.max(1);
+        self.data_mut().par_chunks_mut(width).enumerate()
+    }
+
+    pub fn par_iter_indexed_mut(
+        &mut self,
+    ) -> impl ParallelIterator<Item = ((usize, usize), &mut T)> {
+        self.par_rows_mut().flat_map_iter(|(y, row)| {
+            row.iter_mut()
+                .enumerate()
+                .map(move |(x, pixel)| ((x, y), pixel))
+        })
+    }
+
+    // Disjoint tiles of tile_size, smaller at the right and bottom edges, along with their
+    // rectangle in the texture. Tile views are indexed relative to their top-left corner.
+    pub fn par_tiles_mut(
+        &mut self,
+        tile_size: (usize, usize),
+    ) -> impl ParallelIterator<Item = (Rect<usize>, CpuTextureViewMut<'_, T>)> {
+        assert!(
+            tile_size.0 > 0 && tile_size.1 > 0,
+            "Tile size must not be zero"
+        );
+        let size = self.size;
+        let mut tiles = Vec::new();
+        let mut rest = self.view_mut();
+        for y in (0..size.1).step_by(tile_size.1) {
+            let (mut band, bottom) = rest.split_at_row(tile_size.1.min(size.1 - y));
+            rest = bottom;
+            for x in (0..size.0).step_by(tile_size.0) {
+                let (tile, right) = band.split_at_column(tile_size.0.min(size.0 - x));
+                band = right;
+                tiles.push((Rect::new(x, y, tile.size.0, tile.size.1), tile));
+            }
+        }
+        tiles.into_par_iter()
+    }
+}
+
+impl<T: Sync> CpuTexture<T> {
+    pub fn par_rows(&self) -> impl IndexedParallelIterator<Item = (usize, &[T])> {
+        let width = self.size.0.max(1);
+        self.data().par_chunks(width).enumerate()
+    }
+
+    pub fn par_map<U: Send>(&self, f: impl Fn(&T) -> U + Sync + Send) -> CpuTexture<U> {
+        CpuTexture::new(self.data().par_iter().map(f).collect(), self.size)
+    }
+
+    pub fn par_map_indexed<U: Send>(
+        &self,
+        f: impl Fn((usize, usize), &T) -> U + Sync + Send,
+    ) -> CpuTexture<U> {
+        let width = self.size.0.max(1);
+        let data = self
+            .data()
+            .par_iter()
+            .enumerate()
+            .map(|(i, pixel)| f((i % width, i / width), pixel))
+            .collect();
+        CpuTexture::new(data, self.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern((x, y): (usize, usize)) -> u32 {
+        (x * 31 + y * 1000) as u32
+    }
+
+    #[test]
+    fn from_fn_and_map() {
+        for &size in &[(7, 5), (1, 9), (3, 0)] {
+            let serial = CpuTexture::from_fn(size, pattern);
+            let parallel = CpuTexture::par_from_fn(size, pattern);
+            assert_eq!(parallel.size, serial.size);
+            assert_eq!(parallel.data(), serial.data());
+            let f = |&v: &u32| v as f32 * 0.5;
+            assert_eq!(parallel.par_map(f).data(), serial.map(f).data());
+            let indexed = parallel.par_map_indexed(|(x, y), &v| v + (x * y) as u32);
+            let expected = CpuTexture::from_fn(size, |(x, y)| serial[(x, y)] + (x * y) as u32);
+            assert_eq!(indexed.data(), expected.data());
+        }
+    }
+
+    #[test]
+    fn rows() {
+        let mut texture = CpuTexture::from_fn((4, 3), pattern);
+        let rows = texture
+            .par_rows()
+            .map(|(y, row)| (y, row.to_vec()))
+            .collect::<Vec<_>>();
+        let expected = texture
+            .data()
+            .chunks(4)
+            .map(<[u32]>::to_vec)
+            .enumerate()
+            .collect::<Vec<_>>();
+        assert_eq!(rows, expected);
+        texture
+            .par_rows_mut()
+            .for_each(|(y, row)| row[0] = y as u32);
+        assert_eq!(texture[(0, 2)], 2);
+        assert_eq!(texture[(1, 2)], pattern((1, 2)));
+    }
+
+    #[test]
+    fn iter_indexed_mut() {
+        let mut serial = CpuTexture::new_val(0u32, (6, 4));
+        let mut parallel = CpuTexture::new_val(0u32, (6, 4));
+        for (pos, pixel) in serial.iter_indexed_mut() {
+            *pixel = pattern(pos);
+        }
+        parallel
+            .par_iter_indexed_mut()
+            .for_each(|(pos, pixel)| *pixel = pattern(pos));
+        assert_eq!(parallel.data(), serial.data());
+    }
+
+    #[test]
+    fn tiles_cover_every_pixel_once() {
+        for &tile_size in &[(3, 2), (1, 1), (7, 5), (10, 10)] {
+            let mut texture = CpuTexture::new_val(0u32, (7, 5));
+            texture
+                .par_tiles_mut(tile_size)
+                .for_each(|(rect, mut tile)| {
+                    assert_eq!((rect.width, rect.height), tile.size);
+                    for y in 0..rect.height {
+                        for x in 0..rect.width {
+                            tile[(x, y)] += pattern((rect.x + x, rect.y + y)) + 1;
+                        }
+                    }
+                });
+            let expected = CpuTexture::from_fn((7, 5), |pos| pattern(pos) + 1);
+            assert_eq!(texture.data(), expected.data(), "{:?}", tile_size);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{
+    texture::{luma, CpuTexture, Texture, TextureType},
+    Error,
+};
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+// Pixel types that can be read from and written to PNG files. Reading decodes any PNG to 16-bit
+// RGBA first, then converts to the pixel type. Writing uses the closest PNG format.
+pub trait PngPixel: TextureType {
+    const COLOR_TYPE: ColorType;
+    const BIT_DEPTH: BitDepth;
+    fn from_rgba16(rgba: [u16; 4]) -> Self;
+    // PNG data is big-endian
+    fn write_png_bytes(&self, out: &mut Vec<u8>);
+}
+
+impl PngPixel for [u8; 4] {
+    const COLOR_TYPE: ColorType = ColorType::Rgba;
+    const BIT_DEPTH: BitDepth = BitDepth::Eight;
+    fn from_rgba16(rgba: [u16; 4]) -> Self {
+        [
+            (rgba[0] >> 8) as u8,
+            (rgba[1] >> 8) as u8,
+            (rgba[2] >> 8) as u8,
+            (rgba[3] >> 8) as u8,
+        ]
+    }
+    fn write_png_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl PngPixel for u16 {
+    const COLOR_TYPE: ColorType = ColorType::Grayscale;
+    const BIT_DEPTH: BitDepth = BitDepth::Sixteen;
+    fn from_rgba16(rgba: [u16; 4]) -> Self {
+        luma(rgba)
+    }
+    fn write_png_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl PngPixel for u32 {
+    // saturates to 16 bits when writing
+    const COLOR_TYPE: ColorType = ColorType::Grayscale;
+    const BIT_DEPTH: BitDepth = BitDepth::Sixteen;
+    fn from_rgba16(rgba: [u16; 4]) -> Self {
+        luma(rgba) as u32
+    }
+    fn write_png_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&((*self).min(u16::MAX as u32) as u16).to_be_bytes());
+    }
+}
+
+impl PngPixel for f32 {
+    // clamps to [0, 1] when writing
+    const COLOR_TYPE: ColorType = ColorType::Grayscale;
+    const BIT_DEPTH: BitDepth = BitDepth::Sixteen;
+    fn from_rgba16(rgba: [u16; 4]) -> Self {
+        luma(rgba) as f32 / u16::MAX as f32
+    }
+    fn write_png_bytes(&self, out: &mut Vec<u8>) {
+        let value = (self.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16;
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+impl PngPixel for [f32; 4] {
+    // clamps to [0, 1] when writing
+    const COLOR_TYPE: ColorType = ColorType::Rgba;
+    const BIT_DEPTH: BitDepth = BitDepth::Sixteen;
+    fn from_rgba16(rgba: [u16; 4]) -> Self {
+        let max = u16::MAX as f32;
+        [
+            rgba[0] as f32 / max,
+            rgba[1] as f32 / max,
+            rgba[2] as f32 / max,
+            rgba[3] as f32 / max,
+        ]
+    }
+    fn write_png_bytes(&self, out: &mut Vec<u8>) {
+        for &channel in self {
+            let value = (channel.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16;
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+impl<T: PngPixel> CpuTexture<T> {
+    pub fn load_png(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut decoder = Decoder::new(BufReader::new(File::open(path)?));
+        // palette to rgb, low bit depths to 8 bits, tRNS to alpha
+        decoder.set_transformations(Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        let size = (info.width as usize, info.height as usize);
+        let channels = match info.color_type {
+            ColorType::Grayscale => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+            ColorType::Indexed => return Err("Indexed PNG was not expanded".into()),
+        };
+        let sixteen = info.bit_depth == BitDepth::Sixteen;
+        let bytes_per_channel = if sixteen { 2 } else { 1 };
+        let mut pixels = Vec::with_capacity(size.0 * size.1);
+        for y in 0..size.1 {
+            let line = &buf[y * info.line_size..];
+            for x in 0..size.0 {
+                let mut values = [0u16; 4];
+                for (channel, value) in values.iter_mut().enumerate().take(channels) {
+                    let index = (x * channels + channel) * bytes_per_channel;
+                    *value = if sixteen {
+                        u16::from_be_bytes([line[index], line[index + 1]])
+                    } else {
+                        // 0xAB -> 0xABAB, so that 255 maps to 65535
+                        line[index] as u16 * 257
+                    };
+                }
+                let rgba = match channels {
+                    1 => [values[0], values[0], values[0], u16::MAX],
+                    2 => [values[0], values[0], values[0], values[1]],
+                    3 => [values[0], values[1], values[2], u16::MAX],
+                    _ => values,
+                };
+                pixels.push(T::from_rgba16(rgba));
+            }
+        }
+        Ok(CpuTexture::new(pixels, size))
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = Encoder::new(file, self.size.0 as u32, self.size.1 as u32);
+        encoder.set_color(T::COLOR_TYPE);
+        encoder.set_depth(T::BIT_DEPTH);
+        let mut writer = encoder.write_header()?;
+        let mut data = Vec::new();
+        for pixel in self.data() {
+            pixel.write_png_bytes(&mut data);
+        }
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+impl<T: PngPixel> Texture<T> {
+    pub fn save_png(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.download()?.save_png(path)
+    }
+}
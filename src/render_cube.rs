@@ -0,0 +1,188 @@
+use crate::{
+    check_gl, create_vert_frag_program,
+    cube_texture::{CubeTexture, CUBE_GLSL},
+    render_texture::uniform,
+    texture::TextureType,
+    Error, Rect, GLSL_VERSION,
+};
+use gl::{self, types::*};
+
+#[derive(Clone, Copy, Debug)]
+pub enum CubeRenderMode {
+    // Perspective view from the center of the cube. Angles are in radians, looking down -Z at
+    // yaw = pitch = 0.
+    Skybox { yaw: f32, pitch: f32, fov_y: f32 },
+    // Unfolded horizontal cross: +Y on top, -X +Z +X -Z in the middle row, -Y on the bottom.
+    Cross,
+}
+
+struct CubeProgram {
+    program: GLuint,
+    dst_pos_size_location: GLint,
+    tint_location: GLint,
+}
+
+impl CubeProgram {
+    fn new(frag: &str) -> Result<Self, Error> {
+        let program = create_vert_frag_program(&[VERTEX_SHADER], &[GLSL_VERSION, CUBE_GLSL, frag])?;
+        if !program.success {
+            panic!("Failed to compile shader: {}", program.log);
+        }
+        let program = program.shader;
+        Ok(Self {
+            program,
+            dst_pos_size_location: uniform(program, b"dst_pos_size\0")?,
+            tint_location: uniform(program, b"tint\0")?,
+        })
+    }
+}
+
+pub struct CubeRenderer {
+    skybox: CubeProgram,
+    cross: CubeProgram,
+    rotation_location: GLint,
+    tan_half_fov_location: GLint,
+    dummy_buffer: GLuint,
+}
+
+impl CubeRenderer {
+    pub fn new() -> Result<Self, Error> {
+        check_gl()?;
+        let skybox = CubeProgram::new(FRAGMENT_SHADER_SKYBOX)?;
+        let cross = CubeProgram::new(FRAGMENT_SHADER_CROSS)?;
+        let rotation_location = uniform(skybox.program, b"rotation\0")?;
+        let tan_half_fov_location = uniform(skybox.program, b"tan_half_fov\0")?;
+        let mut dummy_buffer = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut dummy_buffer);
+        }
+        check_gl()?;
+        Ok(Self {
+            skybox,
+            cross,
+            rotation_location,
+            tan_half_fov_location,
+            dummy_buffer,
+        })
+    }
+
+    pub fn render<T: TextureType>(
+        &self,
+        texture: &CubeTexture<T>,
+        mode: CubeRenderMode,
+        dst: Rect<f32>,
+        tint: [f32; 4],
+        screen_size: (f32, f32),
+    ) -> Result<(), Error> {
+        let program = match mode {
+            CubeRenderMode::Skybox { .. } => &self.skybox,
+            CubeRenderMode::Cross => &self.cross,
+        };
+        unsafe {
+            gl::UseProgram(program.program);
+            gl::Uniform4f(
+                program.dst_pos_size_location,
+                dst.x / screen_size.0,
+                dst.y / screen_size.1,
+                dst.width / screen_size.0,
+                dst.height / screen_size.1,
+            );
+            gl::Uniform4f(program.tint_location, tint[0], tint[1], tint[2], tint[3]);
+            if let CubeRenderMode::Skybox { yaw, pitch, fov_y } = mode {
+                let rotation = rotation(yaw, pitch);
+                // rotation is row-major
+                gl::UniformMatrix3fv(self.rotation_location, 1, gl::TRUE, rotation.as_ptr());
+                let tan_half_fov = (fov_y / 2.0).tan();
+                gl::Uniform2f(
+                    self.tan_half_fov_location,
+                    tan_half_fov * dst.width / dst.height,
+                    tan_half_fov,
+                );
+            }
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture.id);
+            gl::BindVertexArray(self.dummy_buffer);
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+            gl::UseProgram(0);
+            check_gl()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CubeRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.dummy_buffer);
+            gl::DeleteProgram(self.skybox.program);
+            gl::DeleteProgram(self.cross.program);
+        }
+    }
+}
+
+// yaw around Y, then pitch around X
+fn rotation(yaw: f32, pitch: f32) -> [f32; 9] {
+    let (sy, cy) = yaw.sin_cos();
+    let (sp, cp) = pitch.sin_cos();
+    [cy, sy * sp, sy * cp, 0.0, cp, -sp, -sy, cy * sp, cy * cp]
+}
+
+const VERTEX_SHADER: &str = "
+#version 450
+
+uniform vec4 dst_pos_size;
+out vec2 texCoord;
+
+void main()
+{
+    float x = (gl_VertexID & 1);
+    float y = (gl_VertexID & 2) >> 1;
+    texCoord = vec2(x, y);
+    float dst_x = dst_pos_size.x + dst_pos_size.z * x;
+    float dst_y = dst_pos_size.y + dst_pos_size.w * y;
+    // flip coordinate space
+    gl_Position = vec4(dst_x*2-1, dst_y*-2+1, 0, 1);
+}
+";
+
+const FRAGMENT_SHADER_SKYBOX: &str = "
+uniform vec4 tint;
+uniform mat3 rotation;
+uniform vec2 tan_half_fov;
+uniform samplerCube tex;
+in vec2 texCoord;
+layout(location = 0) out vec4 out_color;
+
+void main()
+{
+    vec2 ndc = vec2(texCoord.x * 2 - 1, 1 - texCoord.y * 2);
+    vec3 dir = rotation * vec3(ndc * tan_half_fov, -1);
+    out_color = texture(tex, dir) * tint;
+}
+";
+
+const FRAGMENT_SHADER_CROSS: &str = "
+uniform vec4 tint;
+uniform samplerCube tex;
+in vec2 texCoord;
+layout(location = 0) out vec4 out_color;
+
+void main()
+{
+    vec2 cell_pos = texCoord * vec2(4, 3);
+    ivec2 cell = ivec2(floor(cell_pos));
+    vec2 uv = fract(cell_pos) * 2 - 1;
+    int face;
+    if (cell.y == 1) {
+        // -X, +Z, +X, -Z
+        const int middle_row[4] = int[4](1, 4, 0, 5);
+        face = middle_row[clamp(cell.x, 0, 3)];
+    } else if (cell.x == 1) {
+        face = cell.y == 0 ? 2 : 3;
+    } else {
+        discard;
+    }
+    out_color = texture(tex, face_direction(face, uv)) * tint;
+}
+";
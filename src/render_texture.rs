@@ -1,24 +1,38 @@
 use crate::{
-    check_gl, create_vert_frag_program,
-    texture::{CpuTexture, Texture, TextureType},
-    Error, Rect,
+    check_gl,
+    colormap::COLORMAP_GLSL,
+    create_vert_frag_program,
+    stretch::{Stretch, STRETCH_GLSL},
+    texture::{CpuTexture, SamplerKind, Texture, TextureType},
+    Error, Rect, GLSL_VERSION,
 };
 use gl::{self, types::*};
-use std::sync::Once;
+use std::{cell::OnceCell, sync::Once};
 
 // https://rauwendaal.net/2014/06/14/rendering-a-screen-covering-triangle-in-opengl/
 
-pub struct TextureRenderer {
+// One program per SamplerKind, picked from the texture type when rendering and compiled on first
+// use
+struct RendererProgram {
     program: GLuint,
-    dummy_buffer: GLuint,
     src_pos_size_location: GLint,
     dst_pos_size_location: GLint,
     tint_location: GLint,
     scale_offset_location: GLint,
+    stretch_curve_location: GLint,
+    stretch_param_location: GLint,
+    colormap_location: GLint,
+    colormap_channel_location: GLint,
     img_size_location: Option<GLint>,
 }
 
-fn uniform(program: GLuint, var: &[u8]) -> Result<GLint, Error> {
+pub struct TextureRenderer {
+    frag: &'static str,
+    programs: [OnceCell<RendererProgram>; 3],
+    dummy_buffer: GLuint,
+}
+
+pub(crate) fn uniform(program: GLuint, var: &[u8]) -> Result<GLint, Error> {
     assert!(var[var.len() - 1] == 0);
     let location = unsafe { gl::GetUniformLocation(program, var.as_ptr() as *const GLchar) };
     check_gl()?;
@@ -29,19 +43,40 @@ fn uniform(program: GLuint, var: &[u8]) -> Result<GLint, Error> {
     }
 }
 
-impl TextureRenderer {
-    fn impl_new(frag: &str) -> Result<Self, Error> {
-        check_gl()?;
-        let program = create_vert_frag_program(&[VERTEX_SHADER], &[frag])?;
+impl RendererProgram {
+    fn new(frag: &str, sampler: SamplerKind) -> Result<Self, Error> {
+        let program = create_vert_frag_program(
+            &[VERTEX_SHADER],
+            &[
+                GLSL_VERSION,
+                sampler.glsl_define(),
+                STRETCH_GLSL,
+                COLORMAP_GLSL,
+                frag,
+            ],
+        )?;
         if !program.success {
             panic!("Failed to compile shader: {}", program.log);
         }
         let program = program.shader;
-        let src_pos_size_location = uniform(program, b"src_pos_size\0")?;
-        let dst_pos_size_location = uniform(program, b"dst_pos_size\0")?;
-        let tint_location = uniform(program, b"tint\0")?;
-        let scale_offset_location = uniform(program, b"scale_offset\0")?;
-        let img_size_location = uniform(program, b"img_size\0").ok();
+        Ok(Self {
+            program,
+            src_pos_size_location: uniform(program, b"src_pos_size\0")?,
+            dst_pos_size_location: uniform(program, b"dst_pos_size\0")?,
+            tint_location: uniform(program, b"tint\0")?,
+            scale_offset_location: uniform(program, b"scale_offset\0")?,
+            stretch_curve_location: uniform(program, b"stretch_curve\0")?,
+            stretch_param_location: uniform(program, b"stretch_param\0")?,
+            colormap_location: uniform(program, b"colormap\0")?,
+            colormap_channel_location: uniform(program, b"colormap_channel\0")?,
+            img_size_location: uniform(program, b"img_size\0").ok(),
+        })
+    }
+}
+
+impl TextureRenderer {
+    fn impl_new(frag: &'static str) -> Result<Self, Error> {
+        check_gl()?;
         unsafe {
             gl::Enable(gl::BLEND);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
@@ -52,16 +87,21 @@ impl TextureRenderer {
         }
         check_gl()?;
         Ok(Self {
-            program,
+            frag,
+            programs: Default::default(),
             dummy_buffer,
-            src_pos_size_location,
-            dst_pos_size_location,
-            tint_location,
-            scale_offset_location,
-            img_size_location,
         })
     }
 
+    fn program(&self, sampler: SamplerKind) -> Result<&RendererProgram, Error> {
+        let cell = &self.programs[sampler.index()];
+        if let Some(program) = cell.get() {
+            return Ok(program);
+        }
+        let program = RendererProgram::new(self.frag, sampler)?;
+        Ok(cell.get_or_init(|| program))
+    }
+
     pub fn new() -> Result<Self, Error> {
         Self::impl_new(FRAGMENT_SHADER)
     }
@@ -134,7 +174,9 @@ impl Drop for TextureRenderer {
     fn drop(&mut self) {
         unsafe {
             gl::DeleteVertexArrays(1, &self.dummy_buffer);
-            gl::DeleteProgram(self.program);
+            for program in self.programs.iter().filter_map(OnceCell::get) {
+                gl::DeleteProgram(program.program);
+            }
         }
     }
 }
@@ -148,6 +190,8 @@ pub struct RenderBuilder<'renderer, 'texture, T: TextureType> {
     dst: Option<Rect<f32>>,
     tint: Option<[f32; 4]>,
     scale_offset: Option<(f32, f32)>,
+    stretch: Option<Stretch>,
+    colormap: Option<(&'texture Texture<[f32; 4]>, usize)>,
 }
 
 impl<'renderer, 'texture, T: TextureType> RenderBuilder<'renderer, 'texture, T> {
@@ -164,6 +208,8 @@ impl<'renderer, 'texture, T: TextureType> RenderBuilder<'renderer, 'texture, T>
             dst: None,
             tint: None,
             scale_offset: None,
+            stretch: None,
+            colormap: None,
         }
     }
 
@@ -182,11 +228,26 @@ impl<'renderer, 'texture, T: TextureType> RenderBuilder<'renderer, 'texture, T>
         self
     }
 
+    // color * scale + offset. Integer textures are sampled as raw values, so e.g. for u32 data in
+    // 0..4096, (1.0 / 4096.0, 0.0) maps them to 0..1.
     pub fn scale_offset(mut self, scale_offset: (f32, f32)) -> Self {
         self.scale_offset = Some(scale_offset);
         self
     }
 
+    // Replaces scale_offset
+    pub fn stretch(mut self, stretch: Stretch) -> Self {
+        self.stretch = Some(stretch);
+        self
+    }
+
+    // Looks up channel of the (stretched) color in lut, see Colormap::texture
+    pub fn colormap(mut self, lut: &'texture Texture<[f32; 4]>, channel: usize) -> Self {
+        assert!(channel < 4, "Colormap channel {} out of range", channel);
+        self.colormap = Some((lut, channel));
+        self
+    }
+
     pub fn go(mut self) -> Result<(), Error> {
         let src = self.src.take().unwrap_or_else(|| {
             Rect::new(0.0, 0.0, self.texture.size.0 as _, self.texture.size.1 as _)
@@ -195,36 +256,45 @@ impl<'renderer, 'texture, T: TextureType> RenderBuilder<'renderer, 'texture, T>
             Rect::new(0.0, 0.0, self.screen_size.0 as _, self.screen_size.1 as _)
         });
         let tint = self.tint.take().unwrap_or_else(|| [1.0, 1.0, 1.0, 1.0]);
-        let scale_offset = self.scale_offset.take().unwrap_or_else(|| (1.0, 0.0));
+        let (scale_offset, (stretch_curve, stretch_param)) = match self.stretch.take() {
+            Some(stretch) => (stretch.scale_offset(), stretch.curve.uniforms()?),
+            None => (self.scale_offset.take().unwrap_or((1.0, 0.0)), (0, 0.0)),
+        };
+        let program = self.texture_renderer.program(T::sampler())?;
         unsafe {
-            gl::UseProgram(self.texture_renderer.program);
+            gl::UseProgram(program.program);
             gl::Uniform4f(
-                self.texture_renderer.src_pos_size_location,
+                program.src_pos_size_location,
                 src.x / self.texture.size.0 as f32,
                 src.y / self.texture.size.1 as f32,
                 src.width / self.texture.size.0 as f32,
                 src.height / self.texture.size.1 as f32,
             );
             gl::Uniform4f(
-                self.texture_renderer.dst_pos_size_location,
+                program.dst_pos_size_location,
                 dst.x / self.screen_size.0,
                 dst.y / self.screen_size.1,
                 dst.width / self.screen_size.0,
                 dst.height / self.screen_size.1,
             );
-            gl::Uniform4f(
-                self.texture_renderer.tint_location,
-                tint[0],
-                tint[1],
-                tint[2],
-                tint[3],
-            );
+            gl::Uniform4f(program.tint_location, tint[0], tint[1], tint[2], tint[3]);
             gl::Uniform2f(
-                self.texture_renderer.scale_offset_location,
+                program.scale_offset_location,
                 scale_offset.0,
                 scale_offset.1,
             );
-            if let Some(img_size_location) = self.texture_renderer.img_size_location {
+            gl::Uniform1i(program.stretch_curve_location, stretch_curve);
+            gl::Uniform1f(program.stretch_param_location, stretch_param);
+            gl::Uniform1i(program.colormap_location, 1);
+            let colormap_channel = match self.colormap {
+                Some((lut, channel)) => {
+                    gl::BindTextureUnit(1, lut.id);
+                    channel as GLint
+                }
+                None => -1,
+            };
+            gl::Uniform1i(program.colormap_channel_location, colormap_channel);
+            if let Some(img_size_location) = program.img_size_location {
                 gl::Uniform2i(
                     img_size_location,
                     self.texture.size.0 as GLint,
@@ -236,6 +306,7 @@ impl<'renderer, 'texture, T: TextureType> RenderBuilder<'renderer, 'texture, T>
             gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
             gl::BindVertexArray(0);
             gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindTextureUnit(1, 0);
             gl::UseProgram(0);
             check_gl()?;
         }
@@ -281,27 +352,21 @@ void main()
 ";
 
 const FRAGMENT_SHADER: &str = "
-#version 450
-
 uniform vec4 tint;
-uniform vec2 scale_offset;
-uniform sampler2D tex;
+uniform SAMPLER tex;
 in vec2 texCoord;
 layout(location = 0) out vec4 out_color;
 
 void main()
 {
-    vec4 color1 = texture(tex, texCoord) * scale_offset.x + scale_offset.y;
+    vec4 color1 = apply_colormap(stretch(vec4(texture(tex, texCoord))));
     out_color = color1 * tint;
 }
 ";
 
 const FRAGMENT_SHADER_BINNING: &str = "
-#version 450
-
 uniform vec4 tint;
-uniform vec2 scale_offset;
-uniform sampler2D tex;
+uniform SAMPLER tex;
 uniform ivec2 img_size;
 in vec2 texCoord;
 layout(location = 0) out vec4 out_color;
@@ -317,14 +382,14 @@ void main()
     int samples = 0;
     for (int y = img_coords.y; y < next_coords.y; y++) {
         for (int x = img_coords.x; x < next_coords.x; x++) {
-            color1 += texelFetch(tex, ivec2(x, y), 0) * scale_offset.x + scale_offset.y;
+            color1 += vec4(texelFetch(tex, ivec2(x, y), 0));
             samples += 1;
         }
     }
     if (samples > 0) {
-        out_color = color1 * tint / samples;
+        out_color = apply_colormap(stretch(color1 / samples)) * tint;
     } else {
-        out_color = vec4(1.0, 0.0, 1.0, 1.0) * scale_offset.x + scale_offset.y;
+        out_color = apply_colormap(stretch(vec4(1.0, 0.0, 1.0, 1.0)));
     }
 }
 ";
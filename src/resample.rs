@@ -0,0 +1,237 @@
+use crate::{
+    convert::{saturate, PixelConvert},
+    texture::CpuTexture,
+};
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResampleFilter {
+    Nearest,
+    Bilinear,
+    // Catmull-Rom
+    Bicubic,
+    // Lanczos with a = 3
+    Lanczos,
+    // Averages whole source pixels, like FRAGMENT_SHADER_BINNING
+    Area,
+}
+
+impl ResampleFilter {
+    fn support(self) -> f64 {
+        match self {
+            ResampleFilter::Nearest | ResampleFilter::Area => 0.5,
+            ResampleFilter::Bilinear => 1.0,
+            ResampleFilter::Bicubic => 2.0,
+            ResampleFilter::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            ResampleFilter::Nearest | ResampleFilter::Area => (x < 0.5) as u8 as f64,
+            ResampleFilter::Bilinear => (1.0 - x).max(0.0),
+            ResampleFilter::Bicubic => {
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            ResampleFilter::Lanczos => {
+                if x == 0.0 {
+                    1.0
+                } else if x < 3.0 {
+                    let px = PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+// For every destination pixel along one axis: first source pixel and normalized weights
+fn contributions(src_len: usize, dst_len: usize, filter: ResampleFilter) -> Vec<(usize, Vec<f64>)> {
+    let scale = src_len as f64 / dst_len as f64;
+    (0..dst_len)
+        .map(|i| match filter {
+            ResampleFilter::Nearest => {
+                let src = ((i as f64 + 0.5) * scale) as usize;
+                (src.min(src_len - 1), vec![1.0])
+            }
+            ResampleFilter::Area => {
+                let start = ((i as f64 * scale) as usize).min(src_len - 1);
+                let end = (((i + 1) as f64 * scale) as usize).clamp(start + 1, src_len);
+                let count = end - start;
+                (start, vec![1.0 / count as f64; count])
+            }
+            _ => {
+                // stretch the kernel when downscaling so every source pixel contributes
+                let filter_scale = scale.max(1.0);
+                let center = (i as f64 + 0.5) * scale;
+                let support = filter.support() * filter_scale;
+                let start = (center - support).floor().max(0.0) as usize;
+                let end = ((center + support).ceil() as usize).min(src_len);
+                let mut weights = (start..end)
+                    .map(|j| filter.weight((j as f64 + 0.5 - center) / filter_scale))
+                    .collect::<Vec<_>>();
+                let sum = weights.iter().sum::<f64>();
+                if sum != 0.0 {
+                    for weight in &mut weights {
+                        *weight /= sum;
+                    }
+                }
+                (start, weights)
+            }
+        })
+        .collect()
+}
+
+fn resample_axis(
+    src: &CpuTexture<[f64; 4]>,
+    new_len: usize,
+    filter: ResampleFilter,
+    horizontal: bool,
+) -> CpuTexture<[f64; 4]> {
+    let (src_len, size) = if horizontal {
+        (src.size.0, (new_len, src.size.1))
+    } else {
+        (src.size.1, (src.size.0, new_len))
+    };
+    let contributions = contributions(src_len, new_len, filter);
+    CpuTexture::from_fn(size, |(x, y)| {
+        let (start, weights) = &contributions[if horizontal { x } else { y }];
+        let mut accum = [0.0; 4];
+        for (i, &weight) in weights.iter().enumerate() {
+            let pos = if horizontal {
+                (start + i, y)
+            } else {
+                (x, start + i)
+            };
+            for (a, v) in accum.iter_mut().zip(&src[pos]) {
+                *a += v * weight;
+            }
+        }
+        accum
+    })
+}
+
+impl<T: PixelConvert> CpuTexture<T> {
+    // Four channel types are filtered with premultiplied alpha, so fully transparent pixels
+    // don't bleed their color into their neighbours. Filtering is done in f64, so 32-bit
+    // integer values are kept exactly by the nearest filter.
+    pub fn resize(&self, new_size: (usize, usize), filter: ResampleFilter) -> Self {
+        if new_size.0 == 0 || new_size.1 == 0 {
+            return CpuTexture::new(Vec::new(), new_size);
+        }
+        assert!(
+            self.size.0 > 0 && self.size.1 > 0,
+            "Cannot resize empty texture to {:?}",
+            new_size
+        );
+        let premultiply = T::CHANNELS == 4;
+        let src = self.map(|pixel| {
+            let mut accum = pixel.to_channels();
+            if premultiply {
+                let alpha = accum[3];
+                for value in &mut accum[..3] {
+                    *value *= alpha;
+                }
+            }
+            accum
+        });
+        let tmp = resample_axis(&src, new_size.0, filter, true);
+        let result = resample_axis(&tmp, new_size.1, filter, false);
+        result.map(|&accum| {
+            let mut accum = accum;
+            if premultiply {
+                let alpha = accum[3];
+                for value in &mut accum[..3] {
+                    *value = if alpha != 0.0 { *value / alpha } else { 0.0 };
+                }
+            }
+            saturate(accum)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [ResampleFilter; 5] = [
+        ResampleFilter::Nearest,
+        ResampleFilter::Bilinear,
+        ResampleFilter::Bicubic,
+        ResampleFilter::Lanczos,
+        ResampleFilter::Area,
+    ];
+
+    #[test]
+    fn constant_image_at_edges() {
+        let texture = CpuTexture::new_val(0.75f32, (5, 3));
+        for &filter in &FILTERS {
+            for &size in &[(1, 1), (2, 7), (5, 3), (13, 8)] {
+                let resized = texture.resize(size, filter);
+                assert_eq!(resized.size, size);
+                for &value in resized.data() {
+                    assert!(
+                        (value - 0.75).abs() < 1e-5,
+                        "{:?} {:?}: {}",
+                        filter,
+                        size,
+                        value
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bilinear_edges() {
+        let texture = CpuTexture::new(vec![0.0f32, 10.0], (2, 1));
+        let resized = texture.resize((4, 1), ResampleFilter::Bilinear);
+        assert_eq!(resized.data(), &[0.0, 2.5, 7.5, 10.0]);
+    }
+
+    #[test]
+    fn nearest_and_area() {
+        let texture = CpuTexture::new(vec![1u16, 2], (2, 1));
+        let resized = texture.resize((4, 2), ResampleFilter::Nearest);
+        assert_eq!(resized.data(), &[1, 1, 2, 2, 1, 1, 2, 2]);
+        let texture = CpuTexture::new(vec![1.0f32, 3.0, 5.0, 7.0], (4, 1));
+        let resized = texture.resize((2, 1), ResampleFilter::Area);
+        assert_eq!(resized.data(), &[2.0, 6.0]);
+    }
+
+    #[test]
+    fn transparent_pixels_do_not_bleed() {
+        let texture = CpuTexture::new(vec![[255u8, 0, 0, 255], [0, 255, 0, 0]], (2, 1));
+        let resized = texture.resize((1, 1), ResampleFilter::Area);
+        assert_eq!(resized.data(), &[[255, 0, 0, 128]]);
+    }
+
+    #[test]
+    fn empty_size() {
+        let texture = CpuTexture::new_val(1.0f32, (3, 3));
+        assert_eq!(texture.resize((0, 4), ResampleFilter::Bicubic).size, (0, 4));
+    }
+
+    #[test]
+    fn large_integers_are_exact() {
+        let texture = CpuTexture::new(vec![4_000_000_001u32, 16_777_217, 3, u32::MAX], (2, 2));
+        for &filter in &FILTERS {
+            assert_eq!(texture.resize((2, 2), filter).data(), texture.data());
+        }
+        let texture = CpuTexture::new(vec![i32::MIN + 1, 16_777_217], (2, 1));
+        let resized = texture.resize((4, 1), ResampleFilter::Nearest);
+        assert_eq!(
+            resized.data(),
+            &[i32::MIN + 1, i32::MIN + 1, 16_777_217, 16_777_217]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{
+    buffer::{Buffer, Pod},
+    check_gl, Error,
+};
+use gl::types::*;
+use std::ptr::NonNull;
+
+// Fence guarding one region of a RingBuffer until the GPU is done with the commands that read it
+struct Fence(GLsync);
+
+impl Fence {
+    fn new() -> Result<Self, Error> {
+        let sync = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        check_gl()?;
+        Ok(Self(sync))
+    }
+
+    fn wait(&self) -> Result<(), Error> {
+        loop {
+            let result =
+                unsafe { gl::ClientWaitSync(self.0, gl::SYNC_FLUSH_COMMANDS_BIT, 1_000_000) };
+            check_gl()?;
+            match result {
+                gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => return Ok(()),
+                gl::TIMEOUT_EXPIRED => continue,
+                _ => return Err("glClientWaitSync failed".into()),
+            }
+        }
+    }
+}
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSync(self.0);
+        }
+    }
+}
+
+// Buffer split into `regions` equally sized regions, persistently and coherently mapped. Each
+// frame, write to the region returned by next_region, then draw from it (offset region.offset).
+// The next call to next_region fences the previous region, and a region is only handed out again
+// once the GPU has passed its fence, so the CPU never overwrites data the GPU is still reading.
+// 3 regions is the usual choice.
+pub struct RingBuffer<T: Pod> {
+    buffer: Buffer<T>,
+    ptr: NonNull<T>,
+    region_len: usize,
+    fences: Vec<Option<Fence>>,
+    current: usize,
+    // the current region has been handed out and still needs a fence
+    unfenced: bool,
+}
+
+// Element range of the buffer that is safe to write to while it is borrowed
+pub struct RingRegion<'a, T> {
+    pub offset: usize,
+    pub data: &'a mut [T],
+}
+
+impl<T: Pod> RingBuffer<T> {
+    pub fn new(region_len: usize, regions: usize) -> Result<Self, Error> {
+        assert!(
+            region_len > 0 && regions > 0,
+            "Ring buffer needs non-empty regions"
+        );
+        let len = region_len * regions;
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+        let buffer = Buffer::with_storage(len, None, flags)?;
+        let ptr = unsafe {
+            gl::MapNamedBufferRange(buffer.id, 0, buffer.byte_size() as GLsizeiptr, flags)
+        };
+        check_gl()?;
+        let ptr = NonNull::new(ptr as *mut T).ok_or("Failed to map ring buffer")?;
+        let mut fences = Vec::with_capacity(regions);
+        fences.resize_with(regions, || None);
+        Ok(Self {
+            buffer,
+            ptr,
+            region_len,
+            fences,
+            // so that the first next_region returns region 0
+            current: regions - 1,
+            unfenced: false,
+        })
+    }
+
+    // for binding, e.g. VertexArray::bind_buffer_to_bind_index or Buffer::bind_storage_range
+    pub fn buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+
+    pub fn region_len(&self) -> usize {
+        self.region_len
+    }
+
+    pub fn regions(&self) -> usize {
+        self.fences.len()
+    }
+
+    // Advances to the next region, blocking until the GPU is done reading it
+    pub fn next_region(&mut self) -> Result<RingRegion<'_, T>, Error> {
+        if self.unfenced {
+            // the previous region was borrowed until now, so any command reading it has already
+            // been submitted
+            self.fences[self.current] = Some(Fence::new()?);
+            self.unfenced = false;
+        }
+        self.current = (self.current + 1) % self.fences.len();
+        if let Some(fence) = self.fences[self.current].take() {
+            fence.wait()?;
+        }
+        let offset = self.current * self.region_len;
+        // the region is not in use by the GPU, and &mut self keeps other regions from being
+        // handed out while this one is borrowed
+        let data = unsafe {
+            std::slice::from_raw_parts_mut(self.ptr.as_ptr().add(offset), self.region_len)
+        };
+        self.unfenced = true;
+        Ok(RingRegion { offset, data })
+    }
+}
+
+impl<T: Pod> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        if self.unfenced {
+            self.fences[self.current] = Fence::new().ok();
+        }
+        for fence in self.fences.iter().flatten() {
+            // the buffer is deleted right after, make sure the GPU no longer reads it
+            let _ = fence.wait();
+        }
+        unsafe {
+            gl::UnmapNamedBuffer(self.buffer.id);
+        }
+        check_gl().expect("Failed to unmap ring buffer in drop impl");
+    }
+}
@@ -0,0 +1,470 @@
+use crate::{
+    buffer::Buffer,
+    check_gl,
+    convert::PixelConvert,
+    create_compute_program, set_arg_u32,
+    texture::{CpuTexture, SamplerKind, Texture, TextureType},
+    Error, GLSL_VERSION,
+};
+use gl::types::*;
+use std::cell::OnceCell;
+
+// Empty textures have NaN statistics
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelStatistics {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f64,
+    pub std_dev: f64,
+    // the mean of the two middle values for an even count
+    pub median: f32,
+}
+
+// bins evenly divide [min, max], values outside of the range are counted in the first/last bin
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub min: f32,
+    pub max: f32,
+    pub bins: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn new(min: f32, max: f32, bin_count: usize) -> Self {
+        assert!(bin_count > 0, "Histogram needs at least one bin");
+        Self {
+            min,
+            max,
+            bins: vec![0; bin_count],
+        }
+    }
+
+    pub fn bin_of(&self, value: f32) -> usize {
+        let last = self.bins.len() - 1;
+        if self.max <= self.min {
+            return 0;
+        }
+        let bin = (value - self.min) / (self.max - self.min) * self.bins.len() as f32;
+        (bin.max(0.0) as usize).min(last)
+    }
+
+    pub fn add(&mut self, value: f32) {
+        let bin = self.bin_of(value);
+        self.bins[bin] += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.bins.iter().sum()
+    }
+
+    pub fn bin_range(&self, bin: usize) -> (f32, f32) {
+        let width = (self.max - self.min) / self.bins.len() as f32;
+        (
+            self.min + width * bin as f32,
+            self.min + width * (bin + 1) as f32,
+        )
+    }
+
+    // fraction is in [0, 1], interpolated linearly within the bin it lands in
+    pub fn percentile(&self, fraction: f64) -> f32 {
+        let total = self.count();
+        if total == 0 {
+            return self.min;
+        }
+        let target = fraction.clamp(0.0, 1.0) * total as f64;
+        let mut seen = 0.0;
+        for (bin, &count) in self.bins.iter().enumerate() {
+            let next = seen + count as f64;
+            if count != 0 && next >= target {
+                let (low, high) = self.bin_range(bin);
+                let t = ((target - seen) / count as f64) as f32;
+                return low + (high - low) * t;
+            }
+            seen = next;
+        }
+        self.max
+    }
+}
+
+impl<T: PixelConvert> CpuTexture<T> {
+    fn channel_values(&self, channel: usize) -> Vec<f32> {
+        self.data()
+            .iter()
+            .map(|pixel| pixel.to_channels()[channel] as f32)
+            .collect()
+    }
+
+    pub fn statistics(&self) -> Vec<ChannelStatistics> {
+        (0..T::CHANNELS)
+            .map(|channel| {
+                let mut values = self.channel_values(channel);
+                let mut min = f32::NAN;
+                let mut max = f32::NAN;
+                let mut sum = 0.0;
+                for &value in &values {
+                    min = min.min(value);
+                    max = max.max(value);
+                    sum += value as f64;
+                }
+                let mean = sum / values.len() as f64;
+                let variance = values
+                    .iter()
+                    .map(|&value| (value as f64 - mean) * (value as f64 - mean))
+                    .sum::<f64>()
+                    / values.len() as f64;
+                let median = if values.is_empty() {
+                    f32::NAN
+                } else {
+                    let mid = values.len() / 2;
+                    let even = values.len().is_multiple_of(2);
+                    let (lower, &mut upper, _) =
+                        values.select_nth_unstable_by(mid, |a, b| a.total_cmp(b));
+                    if even {
+                        // the lower middle value is the largest one before mid
+                        let lower = lower.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                        ((lower as f64 + upper as f64) * 0.5) as f32
+                    } else {
+                        upper
+                    }
+                };
+                ChannelStatistics {
+                    min,
+                    max,
+                    mean,
+                    std_dev: variance.sqrt(),
+                    median,
+                }
+            })
+            .collect()
+    }
+
+    // One histogram per channel, over range or the min/max of each channel if None
+    pub fn histogram(&self, bin_count: usize, range: Option<(f32, f32)>) -> Vec<Histogram> {
+        (0..T::CHANNELS)
+            .map(|channel| {
+                let values = self.channel_values(channel);
+                let (min, max) = range.unwrap_or_else(|| {
+                    values
+                        .iter()
+                        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| {
+                            (min.min(v), max.max(v))
+                        })
+                });
+                let mut histogram = Histogram::new(min, max, bin_count);
+                for value in values {
+                    histogram.add(value);
+                }
+                histogram
+            })
+            .collect()
+    }
+}
+
+// Floats mapped to uints with the same ordering, so the shader can use atomicMin/atomicMax
+fn encode_ordered(value: f32) -> u32 {
+    let bits = value.to_bits();
+    if bits & 0x8000_0000 != 0 {
+        !bits
+    } else {
+        bits | 0x8000_0000
+    }
+}
+
+fn decode_ordered(bits: u32) -> f32 {
+    if bits & 0x8000_0000 != 0 {
+        f32::from_bits(bits & 0x7FFF_FFFF)
+    } else {
+        f32::from_bits(!bits)
+    }
+}
+
+// Shader storage buffer written by HistogramComputer, laid out as in STATISTICS_GLSL
+pub struct GpuHistogram {
+    pub buffer: Buffer<u32>,
+    pub bin_count: usize,
+}
+
+impl GpuHistogram {
+    const HEADER_WORDS: usize = 8;
+
+    pub fn new(bin_count: usize) -> Result<Self, Error> {
+        assert!(bin_count > 0, "Histogram needs at least one bin");
+        let buffer = Buffer::with_storage(
+            Self::HEADER_WORDS + 4 * bin_count,
+            None,
+            gl::DYNAMIC_STORAGE_BIT,
+        )?;
+        Ok(Self { buffer, bin_count })
+    }
+
+    fn reset(&mut self, range: Option<(f32, f32)>) -> Result<(), Error> {
+        let (min, max) = match range {
+            Some((min, max)) => (encode_ordered(min), encode_ordered(max)),
+            None => (u32::MAX, 0),
+        };
+        let mut words = vec![0u32; self.buffer.len()];
+        words[..4].copy_from_slice(&[min; 4]);
+        words[4..8].copy_from_slice(&[max; 4]);
+        self.buffer.update_range(0, &words)
+    }
+
+    // One histogram per channel of the last computed texture
+    pub fn read(&self, channels: usize) -> Result<Vec<Histogram>, Error> {
+        let words = self.buffer.read()?;
+        let bins = &words[Self::HEADER_WORDS..];
+        Ok((0..channels.min(4))
+            .map(|channel| Histogram {
+                min: decode_ordered(words[channel]),
+                max: decode_ordered(words[4 + channel]),
+                bins: bins[channel * self.bin_count..(channel + 1) * self.bin_count]
+                    .iter()
+                    .map(|&count| count as u64)
+                    .collect(),
+            })
+            .collect())
+    }
+}
+
+// Values are histogrammed as the shader samples them, i.e. normalized for unorm formats
+pub struct HistogramComputer {
+    // (minmax, histogram) per SamplerKind, compiled on first use
+    programs: [OnceCell<(GLuint, GLuint)>; 3],
+}
+
+fn compile(sampler: SamplerKind, shader: &str) -> Result<GLuint, Error> {
+    let program =
+        create_compute_program(&[GLSL_VERSION, sampler.glsl_define(), STATISTICS_GLSL, shader])?;
+    if !program.success {
+        panic!("Failed to compile shader: {}", program.log);
+    }
+    Ok(program.shader)
+}
+
+impl HistogramComputer {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            programs: Default::default(),
+        })
+    }
+
+    fn programs(&self, sampler: SamplerKind) -> Result<(GLuint, GLuint), Error> {
+        let cell = &self.programs[sampler.index()];
+        if let Some(&programs) = cell.get() {
+            return Ok(programs);
+        }
+        let programs = (
+            compile(sampler, MINMAX_COMPUTE_SHADER)?,
+            compile(sampler, HISTOGRAM_COMPUTE_SHADER)?,
+        );
+        Ok(*cell.get_or_init(|| programs))
+    }
+
+    // With range None, the range of each channel is computed on the GPU first
+    pub fn compute<T: TextureType>(
+        &self,
+        texture: &Texture<T>,
+        range: Option<(f32, f32)>,
+        dst: &mut GpuHistogram,
+    ) -> Result<(), Error> {
+        let (minmax, histogram) = self.programs(T::sampler())?;
+        dst.reset(range)?;
+        let mut passes = vec![histogram];
+        if range.is_none() {
+            passes.insert(0, minmax);
+        }
+        for program in passes {
+            set_arg_u32(program, "channels", T::components() as u32)?;
+            set_arg_u32(program, "bin_count", dst.bin_count as u32)?;
+            unsafe {
+                gl::UseProgram(program);
+                gl::BindTextureUnit(0, texture.id);
+                gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, dst.buffer.id);
+                check_gl()?;
+                gl::DispatchCompute(
+                    (texture.size.0 as GLuint).div_ceil(8),
+                    (texture.size.1 as GLuint).div_ceil(8),
+                    1,
+                );
+                gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT | gl::BUFFER_UPDATE_BARRIER_BIT);
+                gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, 0);
+                gl::BindTextureUnit(0, 0);
+                gl::UseProgram(0);
+                check_gl()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HistogramComputer {
+    fn drop(&mut self) {
+        unsafe {
+            for &(minmax, histogram) in self.programs.iter().filter_map(OnceCell::get) {
+                gl::DeleteProgram(minmax);
+                gl::DeleteProgram(histogram);
+            }
+        }
+    }
+}
+
+const STATISTICS_GLSL: &str = "
+layout(local_size_x = 8, local_size_y = 8) in;
+uniform SAMPLER tex;
+uniform uint channels;
+uniform uint bin_count;
+
+layout(std430, binding = 0) buffer HistogramBuffer {
+    uint min_bits[4];
+    uint max_bits[4];
+    // channel-major, bin_count bins per channel
+    uint bins[];
+};
+
+uint encode_ordered(float value)
+{
+    uint bits = floatBitsToUint(value);
+    return (bits & 0x80000000u) != 0 ? ~bits : bits | 0x80000000u;
+}
+
+float decode_ordered(uint bits)
+{
+    return uintBitsToFloat((bits & 0x80000000u) != 0 ? bits & 0x7FFFFFFFu : ~bits);
+}
+
+bool fetch(out vec4 value)
+{
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = textureSize(tex, 0);
+    if (pos.x >= size.x || pos.y >= size.y) {
+        return false;
+    }
+    value = vec4(texelFetch(tex, pos, 0));
+    return true;
+}
+";
+
+const MINMAX_COMPUTE_SHADER: &str = "
+void main()
+{
+    vec4 value;
+    if (!fetch(value)) {
+        return;
+    }
+    for (uint c = 0u; c < channels; c++) {
+        uint bits = encode_ordered(value[c]);
+        atomicMin(min_bits[c], bits);
+        atomicMax(max_bits[c], bits);
+    }
+}
+";
+
+const HISTOGRAM_COMPUTE_SHADER: &str = "
+void main()
+{
+    vec4 value;
+    if (!fetch(value)) {
+        return;
+    }
+    for (uint c = 0u; c < channels; c++) {
+        float low = decode_ordered(min_bits[c]);
+        float high = decode_ordered(max_bits[c]);
+        uint bin = 0u;
+        if (high > low) {
+            float scaled = (value[c] - low) / (high - low) * float(bin_count);
+            bin = uint(clamp(scaled, 0.0, float(bin_count - 1)));
+        }
+        atomicAdd(bins[c * bin_count + bin], 1u);
+    }
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile() {
+        let mut histogram = Histogram::new(0.0, 10.0, 10);
+        for i in 0..10 {
+            histogram.add(i as f32 + 0.5);
+        }
+        assert_eq!(histogram.count(), 10);
+        assert_eq!(histogram.percentile(0.0), 0.0);
+        assert_eq!(histogram.percentile(0.25), 2.5);
+        assert_eq!(histogram.percentile(0.5), 5.0);
+        assert_eq!(histogram.percentile(1.0), 10.0);
+        assert_eq!(histogram.percentile(2.0), 10.0);
+
+        // interpolates within the bin, skipping empty ones
+        let histogram = Histogram {
+            min: 0.0,
+            max: 4.0,
+            bins: vec![0, 4, 0, 0],
+        };
+        assert_eq!(histogram.percentile(0.0), 1.0);
+        assert_eq!(histogram.percentile(0.5), 1.5);
+        assert_eq!(histogram.percentile(1.0), 2.0);
+        assert_eq!(Histogram::new(-1.0, 1.0, 4).percentile(0.5), -1.0);
+    }
+
+    #[test]
+    fn bins() {
+        let mut histogram = Histogram::new(0.0, 1.0, 4);
+        for &value in &[-5.0, 0.0, 0.3, 0.5, 0.99, 1.0, 7.0] {
+            histogram.add(value);
+        }
+        assert_eq!(histogram.bins, vec![2, 1, 1, 3]);
+        assert_eq!(histogram.bin_range(1), (0.25, 0.5));
+        assert_eq!(Histogram::new(1.0, 1.0, 3).bin_of(5.0), 0);
+    }
+
+    #[test]
+    fn statistics() {
+        let texture = CpuTexture::new(vec![4.0f32, 1.0, 3.0, 2.0], (2, 2));
+        let stats = &texture.statistics()[0];
+        assert_eq!((stats.min, stats.max), (1.0, 4.0));
+        assert_eq!(stats.mean, 2.5);
+        assert_eq!(stats.std_dev, 1.25f64.sqrt());
+        assert_eq!(stats.median, 2.5);
+
+        let texture = CpuTexture::new(vec![[9u8, 0, 0, 255], [1, 0, 0, 255], [5, 0, 0, 0]], (3, 1));
+        let stats = texture.statistics();
+        assert_eq!(stats.len(), 4);
+        assert_eq!(stats[0].median, 5.0);
+        assert_eq!(stats[3].median, 255.0);
+
+        let empty = CpuTexture::<f32>::new(Vec::new(), (0, 0));
+        assert!(empty.statistics()[0].median.is_nan());
+    }
+
+    #[test]
+    fn texture_histogram() {
+        let texture = CpuTexture::new(vec![2.0f32, 4.0, 4.0, 6.0], (4, 1));
+        let histogram = &texture.histogram(2, None)[0];
+        assert_eq!((histogram.min, histogram.max), (2.0, 6.0));
+        assert_eq!(histogram.bins, vec![1, 3]);
+        let histogram = &texture.histogram(4, Some((0.0, 8.0)))[0];
+        assert_eq!(histogram.bins, vec![0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn ordered_encoding() {
+        let values = [
+            f32::NEG_INFINITY,
+            -2.5,
+            -0.0,
+            0.0,
+            1e-30,
+            3.0,
+            f32::INFINITY,
+        ];
+        for pair in values.windows(2) {
+            assert!(encode_ordered(pair[0]) <= encode_ordered(pair[1]));
+        }
+        for &value in &values {
+            assert_eq!(
+                decode_ordered(encode_ordered(value)).to_bits(),
+                value.to_bits()
+            );
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    statistics::{ChannelStatistics, Histogram},
+    Error,
+};
+use gl::types::*;
+
+// Applied to rgb after black..white has been mapped to 0..1 and clamped. The parameters of
+// Asinh, Log and Gamma must be positive, rendering fails otherwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StretchCurve {
+    Linear,
+    // asinh(x * beta) / asinh(beta)
+    Asinh(f32),
+    // log(1 + a * x) / log(1 + a)
+    Log(f32),
+    // x ^ (1 / gamma)
+    Gamma(f32),
+    // midtones transfer function with the given midtones balance, MTF(m) = 0.5
+    Mtf(f32),
+}
+
+impl StretchCurve {
+    // values of the stretch_curve and stretch_param uniforms in STRETCH_GLSL
+    pub(crate) fn uniforms(self) -> Result<(GLint, f32), Error> {
+        let (curve, param) = match self {
+            StretchCurve::Linear => return Ok((1, 0.0)),
+            StretchCurve::Asinh(beta) => (2, beta),
+            StretchCurve::Log(a) => (3, a),
+            StretchCurve::Gamma(gamma) => (4, gamma),
+            StretchCurve::Mtf(balance) => return Ok((5, balance)),
+        };
+        // asinh(0) and log(1) are zero denominators, and 1 / 0 is an infinite gamma exponent
+        if param > 0.0 && param.is_finite() {
+            Ok((curve, param))
+        } else {
+            Err(format!("Stretch curve parameter must be positive: {:?}", self).into())
+        }
+    }
+}
+
+pub fn mtf(x: f32, balance: f32) -> f32 {
+    if x <= 0.0 {
+        0.0
+    } else if x >= 1.0 {
+        1.0
+    } else {
+        (balance - 1.0) * x / ((2.0 * balance - 1.0) * x - balance)
+    }
+}
+
+// Display stretch for RenderBuilder::stretch. black and white are in the units the shader
+// samples, so statistics of unorm CpuTextures need to be scaled to 0..1 first, see scaled().
+// Alpha is passed through unchanged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stretch {
+    pub black: f32,
+    pub white: f32,
+    pub curve: StretchCurve,
+}
+
+impl Stretch {
+    pub fn linear(black: f32, white: f32) -> Self {
+        Self {
+            black,
+            white,
+            curve: StretchCurve::Linear,
+        }
+    }
+
+    pub fn min_max(histogram: &Histogram) -> Self {
+        Self::linear(histogram.min, histogram.max)
+    }
+
+    pub fn from_statistics(statistics: &ChannelStatistics) -> Self {
+        Self::linear(statistics.min, statistics.max)
+    }
+
+    // low and high are fractions, e.g. 0.001 and 0.999 to clip hot pixels
+    pub fn percentile(histogram: &Histogram, low: f64, high: f64) -> Self {
+        Self::linear(histogram.percentile(low), histogram.percentile(high))
+    }
+
+    // Screen transfer function as commonly used for astronomical images: clips shadows at
+    // 2.8 normalized MADs below the median, and picks the midtones balance that maps the median
+    // to target_background (0.25 is a good default).
+    pub fn auto_mtf(histogram: &Histogram, target_background: f32) -> Self {
+        let median = histogram.percentile(0.5);
+        let mad = median_absolute_deviation(histogram, median);
+        let black = (median - 2.8 * 1.4826 * mad).max(histogram.min);
+        let white = histogram.max;
+        let normalized_median = if white > black {
+            (median - black) / (white - black)
+        } else {
+            0.5
+        };
+        Self {
+            black,
+            white,
+            curve: StretchCurve::Mtf(mtf(normalized_median, target_background)),
+        }
+    }
+
+    pub fn curve(mut self, curve: StretchCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    // e.g. scaled(1.0 / u16::MAX as f32) for statistics of a CpuTexture<u16>
+    pub fn scaled(mut self, factor: f32) -> Self {
+        self.black *= factor;
+        self.white *= factor;
+        self
+    }
+
+    // (scale, offset) as used by RenderBuilder::scale_offset
+    pub fn scale_offset(&self) -> (f32, f32) {
+        let range = self.white - self.black;
+        if range == 0.0 {
+            (1.0, -self.black)
+        } else {
+            (1.0 / range, -self.black / range)
+        }
+    }
+}
+
+// Approximated from bin centers
+fn median_absolute_deviation(histogram: &Histogram, median: f32) -> f32 {
+    let mut deviations = histogram
+        .bins
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count != 0)
+        .map(|(bin, &count)| {
+            let (low, high) = histogram.bin_range(bin);
+            (((low + high) * 0.5 - median).abs(), count)
+        })
+        .collect::<Vec<_>>();
+    deviations.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let half = histogram.count().div_ceil(2);
+    let mut seen = 0;
+    for (deviation, count) in deviations {
+        seen += count;
+        if seen >= half {
+            return deviation;
+        }
+    }
+    0.0
+}
+
+pub(crate) const STRETCH_GLSL: &str = "
+uniform vec2 scale_offset;
+// 0: only scale_offset, 1: linear, 2: asinh, 3: log, 4: gamma, 5: midtones transfer function
+uniform int stretch_curve;
+uniform float stretch_param;
+
+vec4 stretch(vec4 raw)
+{
+    vec4 color = raw * scale_offset.x + scale_offset.y;
+    if (stretch_curve == 0) {
+        return color;
+    }
+    vec3 x = clamp(color.rgb, 0.0, 1.0);
+    float p = stretch_param;
+    if (stretch_curve == 2) {
+        x = asinh(x * p) / asinh(p);
+    } else if (stretch_curve == 3) {
+        x = log(1.0 + p * x) / log(1.0 + p);
+    } else if (stretch_curve == 4) {
+        x = pow(x, vec3(1.0 / p));
+    } else if (stretch_curve == 5) {
+        x = (p - 1.0) * x / ((2.0 * p - 1.0) * x - p);
+    }
+    return vec4(x, raw.a);
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_parameters() {
+        assert_eq!(StretchCurve::Linear.uniforms().unwrap(), (1, 0.0));
+        assert_eq!(StretchCurve::Asinh(10.0).uniforms().unwrap(), (2, 10.0));
+        assert_eq!(StretchCurve::Log(1000.0).uniforms().unwrap(), (3, 1000.0));
+        assert_eq!(StretchCurve::Gamma(2.2).uniforms().unwrap(), (4, 2.2));
+        assert_eq!(StretchCurve::Mtf(0.25).uniforms().unwrap(), (5, 0.25));
+        for &param in &[0.0, -0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(StretchCurve::Asinh(param).uniforms().is_err(), "{}", param);
+            assert!(StretchCurve::Log(param).uniforms().is_err(), "{}", param);
+            assert!(StretchCurve::Gamma(param).uniforms().is_err(), "{}", param);
+        }
+    }
+}
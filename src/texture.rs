@@ -1,28 +1,93 @@
-use crate::{check_gl, Error};
+use crate::{
+    buffer::{Buffer, Pod},
+    check_gl,
+    framebuffer::Framebuffer,
+    Error, Rect,
+};
 use gl::types::*;
-use std::{ffi::c_void, marker::PhantomData};
+use std::{
+    ffi::c_void,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    marker::PhantomData,
+    ops::Range,
+    path::Path,
+};
+
+// Internal formats in the same view class can be reinterpreted as each other with
+// Texture::view, see the "Compatible internal formats for TextureView" table in the GL spec.
+pub struct ViewClass128Bits;
+pub struct ViewClass32Bits;
+pub struct ViewClass16Bits;
+
+// Which GLSL sampler type a texture has to be read with: sampler2D for float and normalized
+// formats, usampler2D/isampler2D for unnormalized integer formats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplerKind {
+    Float,
+    Unsigned,
+    Signed,
+}
+
+impl SamplerKind {
+    pub const ALL: [SamplerKind; 3] = [
+        SamplerKind::Float,
+        SamplerKind::Unsigned,
+        SamplerKind::Signed,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    // defines SAMPLER as the 2D sampler type, for shaders shared between kinds
+    pub fn glsl_define(self) -> &'static str {
+        match self {
+            SamplerKind::Float => "#define SAMPLER sampler2D\n",
+            SamplerKind::Unsigned => "#define SAMPLER usampler2D\n",
+            SamplerKind::Signed => "#define SAMPLER isampler2D\n",
+        }
+    }
+}
 
 pub trait TextureType: Clone + Default {
+    type ViewClass;
     fn internalformat() -> GLuint;
+    fn sampler() -> SamplerKind {
+        SamplerKind::Float
+    }
     fn size() -> usize {
         std::mem::size_of::<Self>()
     }
+    // number of equally sized scalar components in Self
+    fn components() -> usize {
+        1
+    }
 }
 
 impl TextureType for [f32; 4] {
+    type ViewClass = ViewClass128Bits;
     fn internalformat() -> GLuint {
         gl::RGBA32F
     }
+    fn components() -> usize {
+        4
+    }
 }
 
 impl TextureType for [u8; 4] {
+    type ViewClass = ViewClass32Bits;
     fn internalformat() -> GLuint {
         // normalized integer
         gl::RGBA8
     }
+    fn components() -> usize {
+        4
+    }
 }
 
 impl TextureType for u16 {
+    type ViewClass = ViewClass16Bits;
     fn internalformat() -> GLuint {
         // normalized integer
         gl::R16
@@ -35,11 +100,32 @@ impl TextureType for u16 {
 //     }
 // }
 
+impl TextureType for f32 {
+    type ViewClass = ViewClass32Bits;
+    fn internalformat() -> GLuint {
+        gl::R32F
+    }
+}
+
 impl TextureType for u32 {
+    type ViewClass = ViewClass32Bits;
     fn internalformat() -> GLuint {
         // TODO: GL_R32
         gl::R32UI
     }
+    fn sampler() -> SamplerKind {
+        SamplerKind::Unsigned
+    }
+}
+
+impl TextureType for i32 {
+    type ViewClass = ViewClass32Bits;
+    fn internalformat() -> GLuint {
+        gl::R32I
+    }
+    fn sampler() -> SamplerKind {
+        SamplerKind::Signed
+    }
 }
 
 pub struct Texture<T: TextureType> {
@@ -48,13 +134,50 @@ pub struct Texture<T: TextureType> {
     _t: PhantomData<T>,
 }
 
-fn get_internal_format_info(internalformat: GLenum, property: GLenum) -> Result<GLenum, Error> {
-    let mut result = 0;
+pub(crate) fn get_internal_format_info(
+    internalformat: GLenum,
+    property: GLenum,
+) -> Result<GLenum, Error> {
+    let result = get_internal_format_info_target(gl::TEXTURE_2D, internalformat, property, 1)?;
+    Ok(result[0] as GLenum)
+}
+
+pub(crate) fn get_internal_format_info_target(
+    target: GLenum,
+    internalformat: GLenum,
+    property: GLenum,
+    count: usize,
+) -> Result<Vec<GLint>, Error> {
+    let mut result = vec![0; count];
     unsafe {
-        gl::GetInternalformativ(gl::TEXTURE_2D, internalformat, property, 1, &mut result);
+        gl::GetInternalformativ(
+            target,
+            internalformat,
+            property,
+            count as GLsizei,
+            result.as_mut_ptr(),
+        );
     }
     check_gl()?;
-    Ok(result as GLenum)
+    Ok(result)
+}
+
+pub(crate) fn download_format_type<T: TextureType>() -> Result<(GLenum, GLenum), Error> {
+    let format = get_internal_format_info(T::internalformat(), gl::GET_TEXTURE_IMAGE_FORMAT)?;
+    let type_ = get_internal_format_info(T::internalformat(), gl::GET_TEXTURE_IMAGE_TYPE)?;
+    Ok((format, type_))
+}
+
+pub(crate) fn upload_format_type<T: TextureType>() -> Result<(GLenum, GLenum), Error> {
+    let format = get_internal_format_info(T::internalformat(), gl::TEXTURE_IMAGE_FORMAT)?;
+    let mut type_ = get_internal_format_info(T::internalformat(), gl::TEXTURE_IMAGE_TYPE)?;
+    if T::internalformat() == gl::RGBA8 && type_ == gl::UNSIGNED_NORMALIZED {
+        type_ = gl::UNSIGNED_BYTE
+    }
+    if T::internalformat() == gl::R16 && type_ == gl::UNSIGNED_NORMALIZED {
+        type_ = gl::UNSIGNED_SHORT
+    }
+    Ok((format, type_))
 }
 
 impl<T: TextureType> Texture<T> {
@@ -66,6 +189,13 @@ impl<T: TextureType> Texture<T> {
             check_gl()?;
             gl::TextureStorage2D(texture, 1, format, size.0 as _, size.1 as _);
             check_gl()?;
+        }
+        Self::from_storage(texture, size)
+    }
+
+    // Takes ownership of an already allocated TEXTURE_2D id
+    pub(crate) fn from_storage(texture: GLuint, size: (usize, usize)) -> Result<Self, Error> {
+        unsafe {
             gl::TextureParameteri(texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
             check_gl()?;
             gl::TextureParameteri(texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
@@ -78,11 +208,26 @@ impl<T: TextureType> Texture<T> {
         })
     }
 
+    // Creates a new texture sharing the given mip levels and array layers of self, interpreted as
+    // U. The view is a 2D texture, so it covers exactly one layer, and its size is that of the
+    // first level in levels. GL reference counts the storage, so the view stays valid even if
+    // self is dropped first.
+    pub fn view<U>(&self, levels: Range<u32>, layers: Range<u32>) -> Result<Texture<U>, Error>
+    where
+        U: TextureType<ViewClass = T::ViewClass>,
+    {
+        let size = (
+            self.size.0.checked_shr(levels.start).unwrap_or(0).max(1),
+            self.size.1.checked_shr(levels.start).unwrap_or(0).max(1),
+        );
+        let texture = create_view::<U>(self.id, levels, layers)?;
+        Texture::from_storage(texture, size)
+    }
+
     pub fn download(&mut self) -> Result<CpuTexture<T>, Error> {
         let mut pixels = vec![T::default(); self.size.0 * self.size.1];
         let buf_size = T::size() * pixels.len();
-        let format = get_internal_format_info(T::internalformat(), gl::GET_TEXTURE_IMAGE_FORMAT)?;
-        let type_ = get_internal_format_info(T::internalformat(), gl::GET_TEXTURE_IMAGE_TYPE)?;
+        let (format, type_) = download_format_type::<T>()?;
         unsafe {
             gl::GetTextureImage(
                 self.id,
@@ -97,17 +242,17 @@ impl<T: TextureType> Texture<T> {
         Ok(CpuTexture::new(pixels, self.size))
     }
 
-    pub fn upload(&mut self, cpu_texture: &CpuTexture<T>) -> Result<(), Error> {
-        assert_eq!(self.size, cpu_texture.size);
-        let format = get_internal_format_info(T::internalformat(), gl::TEXTURE_IMAGE_FORMAT)?;
-        let mut type_ = get_internal_format_info(T::internalformat(), gl::TEXTURE_IMAGE_TYPE)?;
-        if T::internalformat() == gl::RGBA8 && type_ == gl::UNSIGNED_NORMALIZED {
-            type_ = gl::UNSIGNED_BYTE
-        }
-        if T::internalformat() == gl::R16 && type_ == gl::UNSIGNED_NORMALIZED {
-            type_ = gl::UNSIGNED_SHORT
-        }
-        unsafe {
+    // Accepts &CpuTexture as well as strided views, which are uploaded without repacking
+    pub fn upload<'a>(&mut self, data: impl Into<CpuTextureView<'a, T>>) -> Result<(), Error>
+    where
+        T: 'a,
+    {
+        let view = data.into();
+        assert_eq!(self.size, view.size);
+        let (format, type_) = upload_format_type::<T>()?;
+        // single rows may have any stride, which GL can't take as a row length
+        let row_length = if view.size.1 > 1 { view.stride } else { 0 };
+        with_unpack_row_length(row_length, || unsafe {
             gl::TextureSubImage2D(
                 self.id,
                 0,
@@ -117,11 +262,9 @@ impl<T: TextureType> Texture<T> {
                 self.size.1 as _,
                 format,
                 type_,
-                cpu_texture.data().as_ptr() as *const c_void,
+                view.as_ptr() as *const c_void,
             );
-            check_gl()?;
-        }
-        Ok(())
+        })
     }
 
     pub fn bind(&self, unit: usize) -> Result<(), Error> {
@@ -152,6 +295,205 @@ impl<T: TextureType> Texture<T> {
             check_gl()
         }
     }
+
+    pub fn clear(&mut self, value: T) -> Result<(), Error> {
+        let (format, type_) = upload_format_type::<T>()?;
+        unsafe {
+            gl::ClearTexImage(
+                self.id,
+                0,
+                format,
+                type_,
+                &value as *const T as *const c_void,
+            );
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    // Copies src_rect of src to dst_pos of self, without any conversion or scaling
+    pub fn copy_from(
+        &mut self,
+        src: &Texture<T>,
+        src_rect: Rect<usize>,
+        dst_pos: (usize, usize),
+    ) -> Result<(), Error> {
+        check_rect(&src_rect, src.size)?;
+        check_rect(
+            &Rect::new(dst_pos.0, dst_pos.1, src_rect.width, src_rect.height),
+            self.size,
+        )?;
+        unsafe {
+            gl::CopyImageSubData(
+                src.id,
+                gl::TEXTURE_2D,
+                0,
+                src_rect.x as GLint,
+                src_rect.y as GLint,
+                0,
+                self.id,
+                gl::TEXTURE_2D,
+                0,
+                dst_pos.0 as GLint,
+                dst_pos.1 as GLint,
+                0,
+                src_rect.width as GLsizei,
+                src_rect.height as GLsizei,
+                1,
+            );
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    // Scales src_rect of src into dst_rect of self. filter: gl::NEAREST or gl::LINEAR (the
+    // latter only for non-integer formats).
+    pub fn blit_from<U: TextureType>(
+        &mut self,
+        src: &Texture<U>,
+        src_rect: Rect<usize>,
+        dst_rect: Rect<usize>,
+        filter: GLenum,
+    ) -> Result<(), Error> {
+        if filter != gl::NEAREST && filter != gl::LINEAR {
+            return Err(format!("Invalid blit filter: {}", filter).into());
+        }
+        check_rect(&src_rect, src.size)?;
+        check_rect(&dst_rect, self.size)?;
+        let src_framebuffer = Framebuffer::with_texture(src)?;
+        let dst_framebuffer = Framebuffer::with_texture(self)?;
+        src_framebuffer.blit(&dst_framebuffer, src_rect, dst_rect, filter)
+    }
+}
+
+pub enum ResizePolicy<T> {
+    // Contents of the new storage are undefined
+    Discard,
+    Clear(T),
+    // Keeps the overlapping top-left region, the rest is cleared to T::default()
+    Copy,
+    // Stretches the old contents over the new size, with gl::NEAREST or gl::LINEAR filtering
+    Scale(GLenum),
+}
+
+impl<T: TextureType> Texture<T> {
+    // Reallocates storage (and therefore id) with a new size. Filtering and swizzle parameters
+    // are carried over to the new storage.
+    pub fn resize(
+        &mut self,
+        new_size: (usize, usize),
+        policy: ResizePolicy<T>,
+    ) -> Result<(), Error> {
+        if new_size == self.size {
+            return match policy {
+                ResizePolicy::Clear(value) => self.clear(value),
+                _ => Ok(()),
+            };
+        }
+        let mut new = Texture::new(new_size)?;
+        match policy {
+            ResizePolicy::Discard => (),
+            ResizePolicy::Clear(value) => new.clear(value)?,
+            ResizePolicy::Copy => {
+                new.clear(T::default())?;
+                let overlap = Rect::new(
+                    0,
+                    0,
+                    self.size.0.min(new_size.0),
+                    self.size.1.min(new_size.1),
+                );
+                new.copy_from(self, overlap, (0, 0))?;
+            }
+            ResizePolicy::Scale(filter) => {
+                let src_rect = Rect::new(0, 0, self.size.0, self.size.1);
+                let dst_rect = Rect::new(0, 0, new_size.0, new_size.1);
+                new.blit_from(self, src_rect, dst_rect, filter)?;
+            }
+        }
+        for &param in &[gl::TEXTURE_MIN_FILTER, gl::TEXTURE_MAG_FILTER] {
+            let mut value = 0;
+            unsafe {
+                gl::GetTextureParameteriv(self.id, param, &mut value);
+                gl::TextureParameteri(new.id, param, value);
+            }
+            check_gl()?;
+        }
+        let mut swizzle = [0; 4];
+        unsafe {
+            gl::GetTextureParameteriv(self.id, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_mut_ptr());
+            gl::TextureParameteriv(new.id, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+        }
+        check_gl()?;
+        // the old storage is deleted when new is dropped
+        std::mem::swap(self, &mut new);
+        Ok(())
+    }
+}
+
+// Runs upload with rows row_length pixels apart and tightly packed (alignment 1), then restores
+// the previous unpack state
+pub(crate) fn with_unpack_row_length(
+    row_length: usize,
+    upload: impl FnOnce(),
+) -> Result<(), Error> {
+    let mut old_row_length = 0;
+    let mut old_alignment = 0;
+    unsafe {
+        gl::GetIntegerv(gl::UNPACK_ROW_LENGTH, &mut old_row_length);
+        gl::GetIntegerv(gl::UNPACK_ALIGNMENT, &mut old_alignment);
+        check_gl()?;
+        gl::PixelStorei(gl::UNPACK_ROW_LENGTH, row_length as GLint);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+    }
+    upload();
+    let result = check_gl();
+    unsafe {
+        gl::PixelStorei(gl::UNPACK_ROW_LENGTH, old_row_length);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, old_alignment);
+    }
+    result?;
+    check_gl()
+}
+
+fn check_rect(rect: &Rect<usize>, size: (usize, usize)) -> Result<(), Error> {
+    if rect.right() > size.0 || rect.bottom() > size.1 {
+        Err(format!("Rect {:?} out of bounds of texture size {:?}", rect, size).into())
+    } else {
+        Ok(())
+    }
+}
+
+// 2D view of orig_texture, so layers has to contain exactly one layer
+pub(crate) fn create_view<U: TextureType>(
+    orig_texture: GLuint,
+    levels: Range<u32>,
+    layers: Range<u32>,
+) -> Result<GLuint, Error> {
+    if levels.is_empty() || layers.end.checked_sub(layers.start) != Some(1) {
+        return Err(format!(
+            "Invalid texture view range: levels {:?}, layers {:?}",
+            levels, layers
+        )
+        .into());
+    }
+    let mut texture = 0;
+    unsafe {
+        // glTextureView requires an unused name that has not been bound yet
+        gl::GenTextures(1, &mut texture);
+        check_gl()?;
+        gl::TextureView(
+            texture,
+            gl::TEXTURE_2D,
+            orig_texture,
+            U::internalformat(),
+            levels.start,
+            levels.end - levels.start,
+            layers.start,
+            1,
+        );
+        check_gl()?;
+    }
+    Ok(texture)
 }
 
 impl<T: TextureType> Drop for Texture<T> {
@@ -181,10 +523,18 @@ impl<T> CpuTexture<T> {
         Self { data, size }
     }
 
+    pub fn from_fn(size: (usize, usize), f: impl FnMut((usize, usize)) -> T) -> Self {
+        Self::new(Range2d::new(size).map(f).collect(), size)
+    }
+
     pub fn data(&self) -> &[T] {
         &self.data[..self.size.0 * self.size.1]
     }
 
+    pub fn data_mut(&mut self) -> &mut [T] {
+        &mut self.data[..self.size.0 * self.size.1]
+    }
+
     pub fn data_too_long(&self) -> bool {
         self.data.len() > self.size.0 * self.size.1
     }
@@ -234,9 +584,752 @@ impl<T> CpuTexture<T> {
         &self[(x as usize, y as usize)]
     }
 
+    // Reflects around the edge pixel without repeating it (-1 -> 1), which preserves the parity
+    // of coordinates, e.g. for bayer patterns
+    pub fn get_mirrored(&self, x: isize, y: isize) -> &T {
+        fn mirror(v: isize, size: usize) -> usize {
+            let size = size as isize;
+            if size == 1 {
+                return 0;
+            }
+            let period = 2 * (size - 1);
+            let v = v.rem_euclid(period);
+            (if v >= size { period - v } else { v }) as usize
+        }
+        &self[(mirror(x, self.size.0), mirror(y, self.size.1))]
+    }
+
+    pub fn get_border<'a>(&'a self, x: isize, y: isize, border: &'a BorderMode<T>) -> &'a T {
+        match border {
+            BorderMode::Clamp => self.get_clamped(x, y),
+            BorderMode::Wrap => self.get_wrapped(x, y),
+            BorderMode::Mirror => self.get_mirrored(x, y),
+            BorderMode::Constant(value) => {
+                if x < 0 || y < 0 || x >= self.size.0 as isize || y >= self.size.1 as isize {
+                    value
+                } else {
+                    &self[(x as usize, y as usize)]
+                }
+            }
+        }
+    }
+
+    pub fn iter_index(&self) -> impl Iterator<Item = (usize, usize)> {
+        Range2d::new(self.size)
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.data_mut().iter_mut()
+    }
+
+    pub fn iter_indexed_mut(&mut self) -> impl Iterator<Item = ((usize, usize), &mut T)> {
+        Range2d::new(self.size).zip(self.data_mut().iter_mut())
+    }
+}
+
+// How to read pixels outside of a CpuTexture, see CpuTexture::get_border
+#[derive(Clone, Debug)]
+pub enum BorderMode<T> {
+    Clamp,
+    Wrap,
+    Mirror,
+    Constant(T),
+}
+
+// Pixel types that can be read from and written to binary netpbm files (PGM, PPM and PAM).
+// Samples are passed around as rgba, scaled to MAXVAL.
+pub trait NetpbmPixel: Sized {
+    const CHANNELS: usize;
+    const MAXVAL: u16;
+    fn from_samples(samples: [u16; 4]) -> Self;
+    fn samples(&self) -> [u16; 4];
+}
+
+impl NetpbmPixel for u16 {
+    const CHANNELS: usize = 1;
+    const MAXVAL: u16 = u16::MAX;
+    fn from_samples(samples: [u16; 4]) -> Self {
+        luma(samples)
+    }
+    fn samples(&self) -> [u16; 4] {
+        [*self, *self, *self, Self::MAXVAL]
+    }
+}
+
+// Netpbm samples are unsigned and at most 16 bits, so values saturate to 0..=65535 when writing
+impl NetpbmPixel for u32 {
+    const CHANNELS: usize = 1;
+    const MAXVAL: u16 = u16::MAX;
+    fn from_samples(samples: [u16; 4]) -> Self {
+        luma(samples) as u32
+    }
+    fn samples(&self) -> [u16; 4] {
+        let value = (*self).min(u16::MAX as u32) as u16;
+        [value, value, value, Self::MAXVAL]
+    }
+}
+
+impl NetpbmPixel for i32 {
+    const CHANNELS: usize = 1;
+    const MAXVAL: u16 = u16::MAX;
+    fn from_samples(samples: [u16; 4]) -> Self {
+        luma(samples) as i32
+    }
+    fn samples(&self) -> [u16; 4] {
+        let value = (*self).clamp(0, u16::MAX as i32) as u16;
+        [value, value, value, Self::MAXVAL]
+    }
+}
+
+impl NetpbmPixel for [u8; 4] {
+    const CHANNELS: usize = 4;
+    const MAXVAL: u16 = u8::MAX as u16;
+    fn from_samples(samples: [u16; 4]) -> Self {
+        [
+            samples[0] as u8,
+            samples[1] as u8,
+            samples[2] as u8,
+            samples[3] as u8,
+        ]
+    }
+    fn samples(&self) -> [u16; 4] {
+        [
+            self[0] as u16,
+            self[1] as u16,
+            self[2] as u16,
+            self[3] as u16,
+        ]
+    }
+}
+
+// Rec. 709 luma of 16-bit (or narrower) rgba samples
+pub(crate) fn luma(rgba: [u16; 4]) -> u16 {
+    (0.2126 * rgba[0] as f32 + 0.7152 * rgba[1] as f32 + 0.0722 * rgba[2] as f32).round() as u16
+}
+
+// Reads the pixel data of an image with the given dimensions (e.g. width, height, bytes per
+// pixel) taken from an untrusted header. Overflowing sizes are rejected, and the buffer grows
+// with the data actually read instead of being allocated up front.
+pub(crate) fn read_image_bytes(reader: &mut impl Read, dims: &[usize]) -> Result<Vec<u8>, Error> {
+    let len = dims
+        .iter()
+        .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+        .ok_or_else(|| format!("Image dimensions {:?} are too large", dims))?;
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(format!(
+            "Image data truncated, expected {} bytes but got {}",
+            len,
+            bytes.len()
+        )
+        .into());
+    }
+    Ok(bytes)
+}
+
+// Whitespace separated header token of netpbm-like formats (also used for PFM), skipping
+// comments
+pub(crate) fn read_netpbm_token(reader: &mut impl Read) -> Result<String, Error> {
+    let mut token = String::new();
+    let mut byte = [0];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] == b'#' && token.is_empty() {
+            while byte[0] != b'\n' {
+                reader.read_exact(&mut byte)?;
+            }
+        } else if byte[0].is_ascii_whitespace() {
+            if !token.is_empty() {
+                return Ok(token);
+            }
+        } else {
+            token.push(byte[0] as char);
+        }
+    }
+}
+
+impl<T: NetpbmPixel> CpuTexture<T> {
+    // Reads binary PGM (P5), PPM (P6) or PAM (P7) with any maxval, rescaling samples to
+    // T::MAXVAL. Missing color channels are replicated from gray, missing alpha is opaque.
+    pub fn read_netpbm(reader: &mut impl Read) -> Result<Self, Error> {
+        let magic = read_netpbm_token(reader)?;
+        let (width, height, depth, maxval) = match &magic[..] {
+            "P5" | "P6" => {
+                let width = read_netpbm_token(reader)?.parse::<usize>()?;
+                let height = read_netpbm_token(reader)?.parse::<usize>()?;
+                let maxval = read_netpbm_token(reader)?.parse::<u32>()?;
+                let depth = if magic == "P5" { 1 } else { 3 };
+                (width, height, depth, maxval)
+            }
+            "P7" => {
+                let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+                loop {
+                    match &read_netpbm_token(reader)?[..] {
+                        "WIDTH" => width = Some(read_netpbm_token(reader)?.parse::<usize>()?),
+                        "HEIGHT" => height = Some(read_netpbm_token(reader)?.parse::<usize>()?),
+                        "DEPTH" => depth = Some(read_netpbm_token(reader)?.parse::<usize>()?),
+                        "MAXVAL" => maxval = Some(read_netpbm_token(reader)?.parse::<u32>()?),
+                        "TUPLTYPE" => {
+                            read_netpbm_token(reader)?;
+                        }
+                        "ENDHDR" => break,
+                        token => return Err(format!("Unknown PAM header: {}", token).into()),
+                    }
+                }
+                match (width, height, depth, maxval) {
+                    (Some(width), Some(height), Some(depth), Some(maxval)) => {
+                        (width, height, depth, maxval)
+                    }
+                    _ => return Err("Incomplete PAM header".into()),
+                }
+            }
+            _ => return Err(format!("Unsupported netpbm magic: {:?}", magic).into()),
+        };
+        if maxval == 0 || maxval > u16::MAX as u32 || depth == 0 || depth > 4 {
+            return Err(format!("Unsupported netpbm depth {} maxval {}", depth, maxval).into());
+        }
+        let bytes_per_sample = if maxval > u8::MAX as u32 { 2 } else { 1 };
+        let bytes = read_image_bytes(reader, &[width, height, depth, bytes_per_sample])?;
+        let rescale = |sample: u32| {
+            ((sample * T::MAXVAL as u32 + maxval / 2) / maxval).min(T::MAXVAL as u32) as u16
+        };
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut values = [0; 4];
+        for pixel in bytes.chunks_exact(depth * bytes_per_sample) {
+            for (value, sample) in values.iter_mut().zip(pixel.chunks_exact(bytes_per_sample)) {
+                // netpbm samples are big-endian
+                *value = if bytes_per_sample == 2 {
+                    rescale(u16::from_be_bytes([sample[0], sample[1]]) as u32)
+                } else {
+                    rescale(sample[0] as u32)
+                };
+            }
+            let opaque = T::MAXVAL;
+            let rgba = match depth {
+                1 => [values[0], values[0], values[0], opaque],
+                2 => [values[0], values[0], values[0], values[1]],
+                3 => [values[0], values[1], values[2], opaque],
+                _ => values,
+            };
+            pixels.push(T::from_samples(rgba));
+        }
+        Ok(CpuTexture::new(pixels, (width, height)))
+    }
+
+    fn write_netpbm_samples(
+        &self,
+        writer: &mut impl Write,
+        channels: &[usize],
+        gray: bool,
+    ) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(self.size.0 * self.size.1 * channels.len() * 2);
+        for pixel in self.data() {
+            let mut samples = pixel.samples();
+            if gray {
+                samples[0] = luma(samples);
+            }
+            for &channel in channels {
+                if T::MAXVAL > u8::MAX as u16 {
+                    bytes.extend_from_slice(&samples[channel].to_be_bytes());
+                } else {
+                    bytes.push(samples[channel] as u8);
+                }
+            }
+        }
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    // Grayscale, converting color pixels with Rec. 709 luma weights
+    pub fn write_pgm(&self, writer: &mut impl Write) -> Result<(), Error> {
+        write!(
+            writer,
+            "P5\n{} {}\n{}\n",
+            self.size.0,
+            self.size.1,
+            T::MAXVAL
+        )?;
+        self.write_netpbm_samples(writer, &[0], T::CHANNELS != 1)
+    }
+
+    // RGB, dropping alpha
+    pub fn write_ppm(&self, writer: &mut impl Write) -> Result<(), Error> {
+        write!(
+            writer,
+            "P6\n{} {}\n{}\n",
+            self.size.0,
+            self.size.1,
+            T::MAXVAL
+        )?;
+        self.write_netpbm_samples(writer, &[0, 1, 2], false)
+    }
+
+    // GRAYSCALE for single-channel pixels, RGB_ALPHA otherwise. Lossless.
+    pub fn write_pam(&self, writer: &mut impl Write) -> Result<(), Error> {
+        let (tupltype, channels): (_, &[usize]) = if T::CHANNELS == 1 {
+            ("GRAYSCALE", &[0])
+        } else {
+            ("RGB_ALPHA", &[0, 1, 2, 3])
+        };
+        write!(
+            writer,
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {}\nENDHDR\n",
+            self.size.0,
+            self.size.1,
+            channels.len(),
+            T::MAXVAL,
+            tupltype
+        )?;
+        self.write_netpbm_samples(writer, channels, false)
+    }
+
+    pub fn load_netpbm(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read_netpbm(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save_pgm(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_pgm(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn save_ppm(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_ppm(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn save_pam(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_pam(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+const RAW_MAGIC: &[u8; 8] = b"KHYGLRAW";
+
+// Raw dumps: RAW_MAGIC, then little-endian u32 internalformat, u32 bytes per pixel, u64 width,
+// u64 height, followed by the pixel data with each component in little-endian. Pod is required
+// because the pixels are copied as bytes.
+impl<T: TextureType + Pod> CpuTexture<T> {
+    fn raw_bytes_to_le(bytes: &mut [u8]) {
+        if cfg!(target_endian = "big") {
+            let component_size = T::size() / T::components();
+            for component in bytes.chunks_exact_mut(component_size) {
+                component.reverse();
+            }
+        }
+    }
+
+    pub fn write_raw(&self, writer: &mut impl Write) -> Result<(), Error> {
+        writer.write_all(RAW_MAGIC)?;
+        writer.write_all(&T::internalformat().to_le_bytes())?;
+        writer.write_all(&(T::size() as u32).to_le_bytes())?;
+        writer.write_all(&(self.size.0 as u64).to_le_bytes())?;
+        writer.write_all(&(self.size.1 as u64).to_le_bytes())?;
+        let data = self.data();
+        // T: Pod, so every byte of data is initialized
+        let mut bytes = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
+        }
+        .to_vec();
+        Self::raw_bytes_to_le(&mut bytes);
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn read_raw(reader: &mut impl Read) -> Result<Self, Error> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != RAW_MAGIC {
+            return Err("Not a khygl raw texture dump".into());
+        }
+        let mut u32_bytes = [0; 4];
+        let mut u64_bytes = [0; 8];
+        reader.read_exact(&mut u32_bytes)?;
+        let internalformat = u32::from_le_bytes(u32_bytes);
+        reader.read_exact(&mut u32_bytes)?;
+        let pixel_size = u32::from_le_bytes(u32_bytes);
+        if internalformat != T::internalformat() || pixel_size as usize != T::size() {
+            return Err(format!(
+                "Raw dump has format {:#x} ({} bytes per pixel), expected {:#x} ({} bytes)",
+                internalformat,
+                pixel_size,
+                T::internalformat(),
+                T::size()
+            )
+            .into());
+        }
+        reader.read_exact(&mut u64_bytes)?;
+        let width = u64::from_le_bytes(u64_bytes) as usize;
+        reader.read_exact(&mut u64_bytes)?;
+        let height = u64::from_le_bytes(u64_bytes) as usize;
+        let mut bytes = read_image_bytes(reader, &[width, height, T::size()])?;
+        Self::raw_bytes_to_le(&mut bytes);
+        let mut pixels = vec![T::default(); width * height];
+        // T: Pod, so any bytes read are a valid T
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                pixels.as_mut_ptr() as *mut u8,
+                std::mem::size_of_val(&pixels[..]),
+            )
+        }
+        .copy_from_slice(&bytes);
+        Ok(CpuTexture::new(pixels, (width, height)))
+    }
+
+    pub fn load_raw(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read_raw(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save_raw(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_raw(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// Borrowed rectangle of pixels, rows are stride elements apart. Used for zero-copy crops of a
+// CpuTexture and for wrapping buffers owned by someone else. Only the pixels inside the
+// rectangle are borrowed: the gaps between rows can belong to other views, e.g. the tiles of
+// CpuTextureViewMut::split_at_column.
+#[derive(Debug)]
+pub struct CpuTextureView<'a, T> {
+    // first pixel
+    ptr: *const T,
+    pub size: (usize, usize),
+    pub stride: usize,
+    _data: PhantomData<&'a [T]>,
+}
+
+// not derived, that would require T: Copy
+impl<'a, T> Clone for CpuTextureView<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for CpuTextureView<'a, T> {}
+
+// views behave like &[T] and &mut [T] of their pixels
+unsafe impl<'a, T: Sync> Send for CpuTextureView<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for CpuTextureView<'a, T> {}
+
+#[derive(Debug)]
+pub struct CpuTextureViewMut<'a, T> {
+    // first pixel
+    ptr: *mut T,
+    pub size: (usize, usize),
+    pub stride: usize,
+    _data: PhantomData<&'a mut [T]>,
+}
+
+unsafe impl<'a, T: Send> Send for CpuTextureViewMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for CpuTextureViewMut<'a, T> {}
+
+fn check_view(len: usize, offset: usize, size: (usize, usize), stride: usize) {
+    // with a single row the stride is never used, so rows can't overlap
+    assert!(
+        stride >= size.0 || size.1 <= 1,
+        "Stride {} is less than width {}",
+        stride,
+        size.0
+    );
+    let required = if size.0 == 0 || size.1 == 0 {
+        Some(0)
+    } else {
+        (size.1 - 1)
+            .checked_mul(stride)
+            .and_then(|rows| rows.checked_add(offset))
+            .and_then(|end| end.checked_add(size.0))
+    };
+    let required = required.unwrap_or_else(|| {
+        panic!(
+            "View overflows: offset {}, size {:?}, stride {}",
+            offset, size, stride
+        )
+    });
+    assert!(
+        len >= required,
+        "Buffer too small for view: {} < {} (offset {}, size {:?}, stride {})",
+        len,
+        required,
+        offset,
+        size,
+        stride
+    );
+}
+
+fn view_rect_offset(size: (usize, usize), stride: usize, rect: &Rect<usize>) -> usize {
+    assert!(
+        rect.right() <= size.0 && rect.bottom() <= size.1,
+        "View {:?} out of range (size {:?})",
+        rect,
+        size
+    );
+    rect.y * stride + rect.x
+}
+
+fn check_index(index: (usize, usize), size: (usize, usize)) {
+    if index.0 >= size.0 || index.1 >= size.1 {
+        panic!("Index out of range: {:?} (size {:?})", index, size)
+    }
+}
+
+fn check_row(y: usize, size: (usize, usize)) {
+    assert!(y < size.1, "Row {} out of range (size {:?})", y, size);
+}
+
+impl<'a, T> CpuTextureView<'a, T> {
+    pub fn new(data: &'a [T], size: (usize, usize)) -> Self {
+        Self::with_stride(data, 0, size, size.0)
+    }
+
+    // offset and stride are in elements of T, not bytes
+    pub fn with_stride(data: &'a [T], offset: usize, size: (usize, usize), stride: usize) -> Self {
+        check_view(data.len(), offset, size, stride);
+        Self {
+            ptr: data.as_ptr().wrapping_add(offset.min(data.len())),
+            size,
+            stride,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &'a [T] {
+        check_row(y, self.size);
+        // in bounds by check_view, and only covering pixels of this view
+        unsafe { std::slice::from_raw_parts(self.ptr.wrapping_add(y * self.stride), self.size.0) }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [T]> {
+        let view = *self;
+        (0..self.size.1).map(move |y| view.row(y))
+    }
+
+    pub fn sub_view(&self, rect: Rect<usize>) -> CpuTextureView<'a, T> {
+        let offset = view_rect_offset(self.size, self.stride, &rect);
+        Self {
+            ptr: self.ptr.wrapping_add(offset),
+            size: (rect.width, rect.height),
+            stride: self.stride,
+            _data: PhantomData,
+        }
+    }
+
+    // rows are contiguous, so the whole view can be passed around as a single slice
+    pub fn is_contiguous(&self) -> bool {
+        self.stride == self.size.0 || self.size.1 <= 1
+    }
+
     pub fn iter_index(&self) -> impl Iterator<Item = (usize, usize)> {
         Range2d::new(self.size)
     }
+
+    // first pixel, for handing the view to GL together with its stride
+    pub(crate) fn as_ptr(&self) -> *const T {
+        self.ptr
+    }
+}
+
+impl<'a, T: Clone> CpuTextureView<'a, T> {
+    pub fn to_texture(&self) -> CpuTexture<T> {
+        let mut data = Vec::with_capacity(self.size.0 * self.size.1);
+        for row in self.rows() {
+            data.extend_from_slice(row);
+        }
+        CpuTexture::new(data, self.size)
+    }
+}
+
+impl<'a, T> CpuTextureViewMut<'a, T> {
+    pub fn new(data: &'a mut [T], size: (usize, usize)) -> Self {
+        Self::with_stride(data, 0, size, size.0)
+    }
+
+    // offset and stride are in elements of T, not bytes
+    pub fn with_stride(
+        data: &'a mut [T],
+        offset: usize,
+        size: (usize, usize),
+        stride: usize,
+    ) -> Self {
+        check_view(data.len(), offset, size, stride);
+        let offset = offset.min(data.len());
+        Self {
+            ptr: data.as_mut_ptr().wrapping_add(offset),
+            size,
+            stride,
+            _data: PhantomData,
+        }
+    }
+
+    fn with_ptr(ptr: *mut T, size: (usize, usize), stride: usize) -> Self {
+        Self {
+            ptr,
+            size,
+            stride,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn as_view(&self) -> CpuTextureView<'_, T> {
+        CpuTextureView {
+            ptr: self.ptr,
+            size: self.size,
+            stride: self.stride,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        self.as_view().row(y)
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        check_row(y, self.size);
+        // rows of a view don't overlap, and &mut self keeps them from being borrowed twice
+        unsafe {
+            std::slice::from_raw_parts_mut(self.ptr.wrapping_add(y * self.stride), self.size.0)
+        }
+    }
+
+    pub fn sub_view_mut(&mut self, rect: Rect<usize>) -> CpuTextureViewMut<'_, T> {
+        let offset = view_rect_offset(self.size, self.stride, &rect);
+        CpuTextureViewMut::with_ptr(
+            self.ptr.wrapping_add(offset),
+            (rect.width, rect.height),
+            self.stride,
+        )
+    }
+
+    // Splits into the rows above y and the rows starting at y, e.g. to hand them to different
+    // threads
+    pub fn split_at_row(self, y: usize) -> (CpuTextureViewMut<'a, T>, CpuTextureViewMut<'a, T>) {
+        assert!(
+            y <= self.size.1,
+            "Row {} out of range (size {:?})",
+            y,
+            self.size
+        );
+        (
+            Self::with_ptr(self.ptr, (self.size.0, y), self.stride),
+            Self::with_ptr(
+                self.ptr.wrapping_add(y * self.stride),
+                (self.size.0, self.size.1 - y),
+                self.stride,
+            ),
+        )
+    }
+
+    // Splits into the columns left of x and the columns starting at x
+    pub fn split_at_column(self, x: usize) -> (CpuTextureViewMut<'a, T>, CpuTextureViewMut<'a, T>) {
+        assert!(
+            x <= self.size.0,
+            "Column {} out of range (size {:?})",
+            x,
+            self.size
+        );
+        (
+            Self::with_ptr(self.ptr, (x, self.size.1), self.stride),
+            Self::with_ptr(
+                self.ptr.wrapping_add(x),
+                (self.size.0 - x, self.size.1),
+                self.stride,
+            ),
+        )
+    }
+
+    pub fn iter_indexed_mut(&mut self) -> impl Iterator<Item = ((usize, usize), &mut T)> {
+        let (ptr, width, stride) = (self.ptr, self.size.0, self.stride);
+        (0..self.size.1).flat_map(move |y| {
+            // rows are disjoint, and borrowed from &mut self
+            let row =
+                unsafe { std::slice::from_raw_parts_mut(ptr.wrapping_add(y * stride), width) };
+            row.iter_mut()
+                .enumerate()
+                .map(move |(x, pixel)| ((x, y), pixel))
+        })
+    }
+}
+
+impl<'a, T: Clone> CpuTextureViewMut<'a, T> {
+    pub fn fill(&mut self, value: T) {
+        for y in 0..self.size.1 {
+            for pixel in self.row_mut(y) {
+                *pixel = value.clone();
+            }
+        }
+    }
+
+    pub fn copy_from(&mut self, src: &CpuTextureView<'_, T>) {
+        assert_eq!(self.size, src.size);
+        for y in 0..self.size.1 {
+            self.row_mut(y).clone_from_slice(src.row(y));
+        }
+    }
+}
+
+impl<'a, T> std::ops::Index<(usize, usize)> for CpuTextureView<'a, T> {
+    type Output = T;
+    fn index(&self, index: (usize, usize)) -> &T {
+        check_index(index, self.size);
+        unsafe { &*self.ptr.wrapping_add(self.stride * index.1 + index.0) }
+    }
+}
+
+impl<'a, T> std::ops::Index<(usize, usize)> for CpuTextureViewMut<'a, T> {
+    type Output = T;
+    fn index(&self, index: (usize, usize)) -> &T {
+        check_index(index, self.size);
+        unsafe { &*self.ptr.wrapping_add(self.stride * index.1 + index.0) }
+    }
+}
+
+impl<'a, T> std::ops::IndexMut<(usize, usize)> for CpuTextureViewMut<'a, T> {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut T {
+        check_index(index, self.size);
+        unsafe { &mut *self.ptr.wrapping_add(self.stride * index.1 + index.0) }
+    }
+}
+
+impl<'a, T> From<&'a CpuTexture<T>> for CpuTextureView<'a, T> {
+    fn from(texture: &'a CpuTexture<T>) -> Self {
+        texture.view()
+    }
+}
+
+impl<'a, 'b, T> From<&'b CpuTextureViewMut<'a, T>> for CpuTextureView<'b, T> {
+    fn from(view: &'b CpuTextureViewMut<'a, T>) -> Self {
+        view.as_view()
+    }
+}
+
+impl<T> CpuTexture<T> {
+    pub fn view(&self) -> CpuTextureView<'_, T> {
+        CpuTextureView::new(self.data(), self.size)
+    }
+
+    pub fn view_mut(&mut self) -> CpuTextureViewMut<'_, T> {
+        let size = self.size;
+        CpuTextureViewMut::new(self.data_mut(), size)
+    }
+
+    // like crop, without copying
+    pub fn view_rect(&self, rect: Rect<usize>) -> CpuTextureView<'_, T> {
+        self.view().sub_view(rect)
+    }
+
+    pub fn view_rect_mut(&mut self, rect: Rect<usize>) -> CpuTextureViewMut<'_, T> {
+        let size = self.size;
+        let offset = view_rect_offset(size, size.0, &rect);
+        CpuTextureViewMut::with_stride(self.data_mut(), offset, (rect.width, rect.height), size.0)
+    }
 }
 
 pub fn offset(
@@ -321,167 +1414,154 @@ impl Iterator for Range2d {
     }
 }
 
-pub struct VertexBuffer<T> {
-    pub id: GLuint,
-    _t: PhantomData<T>,
-}
+// Kept for compatibility, see Buffer
+pub type VertexBuffer<T> = Buffer<T>;
 
-impl<T> VertexBuffer<T> {
-    pub fn new() -> Result<Self, Error> {
-        let mut id = 0;
-        unsafe {
-            gl::CreateBuffers(1, &mut id);
-            check_gl()?;
-        }
-        Ok(Self {
-            id,
-            _t: PhantomData,
-        })
-    }
+// Kept for compatibility, see vertex_array
+pub use crate::vertex_array::VertexArray;
 
-    pub fn set_data(&mut self, data: &[T], usage: GLenum) -> Result<(), Error> {
-        // usage must be: GL_STREAM_DRAW, GL_STREAM_READ, GL_STREAM_COPY, GL_STATIC_DRAW, GL_STATIC_READ, GL_STATIC_COPY, GL_DYNAMIC_DRAW, GL_DYNAMIC_READ, or GL_DYNAMIC_COPY
-        unsafe {
-            gl::NamedBufferData(self.id, data.len() as GLsizeiptr, data.as_ptr() as _, usage);
-            check_gl()?;
-        }
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba_texture() -> CpuTexture<[u8; 4]> {
+        CpuTexture::new(
+            vec![
+                [0, 1, 2, 255],
+                [255, 128, 7, 0],
+                [10, 20, 30, 40],
+                [9, 9, 9, 9],
+            ],
+            (2, 2),
+        )
     }
-}
 
-impl<T> Drop for VertexBuffer<T> {
-    fn drop(&mut self) {
-        unsafe {
-            gl::DeleteBuffers(1, &self.id);
-            check_gl().expect("Failed to delete buffer in drop impl");
+    #[test]
+    fn netpbm_round_trip() {
+        let texture = CpuTexture::new(vec![0u16, 1, 300, 32768, 65535, 12345], (3, 2));
+        for write in &[CpuTexture::<u16>::write_pgm, CpuTexture::<u16>::write_pam] {
+            let mut bytes = Vec::new();
+            write(&texture, &mut bytes).unwrap();
+            let read = CpuTexture::<u16>::read_netpbm(&mut &bytes[..]).unwrap();
+            assert_eq!(read.size, texture.size);
+            assert_eq!(read.data(), texture.data());
         }
+
+        let texture = rgba_texture();
+        let mut bytes = Vec::new();
+        texture.write_pam(&mut bytes).unwrap();
+        assert_eq!(
+            CpuTexture::<[u8; 4]>::read_netpbm(&mut &bytes[..])
+                .unwrap()
+                .data(),
+            texture.data()
+        );
+        let mut bytes = Vec::new();
+        texture.write_ppm(&mut bytes).unwrap();
+        let read = CpuTexture::<[u8; 4]>::read_netpbm(&mut &bytes[..]).unwrap();
+        assert_eq!(read[(1, 0)], [255, 128, 7, 255]);
     }
-}
 
-pub struct VertexArray {
-    pub id: GLuint,
-}
+    #[test]
+    fn netpbm_rescale() {
+        let mut bytes = b"P5\n# comment\n3 1\n255\n".to_vec();
+        bytes.extend_from_slice(&[0, 128, 255]);
+        let read = CpuTexture::<u16>::read_netpbm(&mut &bytes[..]).unwrap();
+        assert_eq!(read.data(), &[0, 32896, 65535]);
 
-// Steps:
-// 0) gl::BindAttribLocation on the shader to associate attrib_index to variable name
-// 1) bind VertexBuffer to a bind_index using bind_buffer_to_bind_index
-// 2) associate a attrib_index to a bind_index using associate_attrib_index_to_bind_index
-// 3) specify the format of an attrib_index using attrib_format_*
-// 4) gl::BindVertexArray to draw with gl::DrawArrays
-impl VertexArray {
-    pub fn new() -> Result<Self, Error> {
-        let mut id = 0;
-        unsafe {
-            gl::CreateVertexArrays(1, &mut id);
-            check_gl()?;
-        }
-        Ok(Self { id })
+        let mut bytes = b"P5 2 1 1000 ".to_vec();
+        bytes.extend_from_slice(&500u16.to_be_bytes());
+        bytes.extend_from_slice(&1000u16.to_be_bytes());
+        let read = CpuTexture::<[u8; 4]>::read_netpbm(&mut &bytes[..]).unwrap();
+        assert_eq!(read.data(), &[[128, 128, 128, 255], [255; 4]]);
     }
 
-    pub fn enable_attrib(&self, attrib_index: GLuint) -> Result<(), Error> {
-        unsafe {
-            gl::EnableVertexArrayAttrib(self.id, attrib_index);
-            check_gl()?;
-        }
-        Ok(())
+    #[test]
+    fn netpbm_invalid() {
+        let bytes = b"P5 4 4 255 \x01\x02\x03";
+        assert!(CpuTexture::<u16>::read_netpbm(&mut &bytes[..]).is_err());
+        let header = format!("P6 {} {} 255 ", usize::MAX, usize::MAX);
+        assert!(CpuTexture::<u16>::read_netpbm(&mut header.as_bytes()).is_err());
+        let bytes = b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 5\nMAXVAL 255\nENDHDR\n\0\0\0\0\0";
+        assert!(CpuTexture::<u16>::read_netpbm(&mut &bytes[..]).is_err());
     }
 
-    pub fn disable_attrib(&self, attrib_index: GLuint) -> Result<(), Error> {
-        unsafe {
-            gl::DisableVertexArrayAttrib(self.id, attrib_index);
-            check_gl()?;
-        }
-        Ok(())
-    }
+    #[test]
+    fn raw_round_trip() {
+        let texture = rgba_texture();
+        let mut bytes = Vec::new();
+        texture.write_raw(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 32 + 16);
+        assert_eq!(
+            CpuTexture::<[u8; 4]>::read_raw(&mut &bytes[..])
+                .unwrap()
+                .data(),
+            texture.data()
+        );
 
-    pub fn bind_buffer_to_bind_index<T>(
-        &self,
-        bind_index: GLuint,
-        buffer: &VertexBuffer<T>,
-        offset: GLintptr,
-        stride: GLsizei,
-    ) -> Result<(), Error> {
-        unsafe {
-            gl::VertexArrayVertexBuffer(self.id, bind_index, buffer.id, offset, stride);
-            check_gl()?;
-        }
-        Ok(())
+        let texture = CpuTexture::new(vec![-1.5f32, 0.0, 1e30], (1, 3));
+        let mut bytes = Vec::new();
+        texture.write_raw(&mut bytes).unwrap();
+        let read = CpuTexture::<f32>::read_raw(&mut &bytes[..]).unwrap();
+        assert_eq!(read.size, (1, 3));
+        assert_eq!(read.data(), texture.data());
+        // the format is checked, even when the pixel size matches
+        assert!(CpuTexture::<u32>::read_raw(&mut &bytes[..]).is_err());
+        assert!(CpuTexture::<f32>::read_raw(&mut &bytes[..bytes.len() - 1]).is_err());
     }
 
-    pub fn associate_attrib_index_to_bind_index(
-        &self,
-        attrib_index: GLuint,
-        bind_index: GLuint,
-    ) -> Result<(), Error> {
-        unsafe {
-            gl::VertexArrayAttribBinding(self.id, attrib_index, bind_index);
-            check_gl()?;
-        }
-        Ok(())
+    fn raw_header(width: u64, height: u64) -> Vec<u8> {
+        let mut bytes = RAW_MAGIC.to_vec();
+        bytes.extend_from_slice(&u16::internalformat().to_le_bytes());
+        bytes.extend_from_slice(&(u16::size() as u32).to_le_bytes());
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes
     }
 
-    // size: num elements per vertex
-    // type: gl::FLOAT, etc.
-    // "relativeoffset is the offset, measured in basic machine units of the first element relative to the start of the vertex buffer binding this attribute fetches from."
-    pub fn attrib_format_float(
-        &self,
-        attrib_index: GLuint,
-        size: GLint,
-        type_: GLenum,
-        normalized: bool,
-        relative_offset: GLuint,
-    ) -> Result<(), Error> {
-        unsafe {
-            let normalized = if normalized { gl::TRUE } else { gl::FALSE };
-            gl::VertexArrayAttribFormat(
-                self.id,
-                attrib_index,
-                size,
-                type_,
-                normalized,
-                relative_offset,
-            );
-            check_gl()?;
-        }
-        Ok(())
+    #[test]
+    fn raw_too_large() {
+        let bytes = raw_header(u64::MAX, 2);
+        assert!(CpuTexture::<u16>::read_raw(&mut &bytes[..]).is_err());
+        // fits in usize, but is never allocated as the data is missing
+        let mut bytes = raw_header(1 << 40, 1 << 10);
+        bytes.extend_from_slice(&[0; 64]);
+        assert!(CpuTexture::<u16>::read_raw(&mut &bytes[..]).is_err());
     }
 
-    pub fn attrib_format_int(
-        &self,
-        attrib_index: GLuint,
-        size: GLint,
-        type_: GLenum,
-        relative_offset: GLuint,
-    ) -> Result<(), Error> {
-        unsafe {
-            gl::VertexArrayAttribIFormat(self.id, attrib_index, size, type_, relative_offset);
-            check_gl()?;
-        }
-        Ok(())
+    #[test]
+    fn view_stride() {
+        let data = (0..12u8).collect::<Vec<_>>();
+        let view = CpuTextureView::with_stride(&data, 1, (2, 3), 4);
+        assert_eq!(
+            view.rows().collect::<Vec<_>>(),
+            [&[1, 2], &[5, 6], &[9, 10]]
+        );
+        // a single row never steps by the stride
+        let view = CpuTextureView::with_stride(&data, 10, (2, 1), usize::MAX);
+        assert_eq!(view.row(0), &[10, 11]);
+        let view = CpuTextureView::with_stride(&data, 0, (3, 1), 0);
+        assert_eq!(view.row(0), &[0, 1, 2]);
     }
 
-    pub fn bind(&self) -> Result<(), Error> {
-        unsafe {
-            gl::BindVertexArray(self.id);
-            check_gl()?;
-        }
-        Ok(())
+    #[test]
+    #[should_panic(expected = "View overflows")]
+    fn view_stride_overflow() {
+        let data = [0u8; 16];
+        CpuTextureView::with_stride(&data, 0, (2, 2), usize::MAX);
     }
 
-    pub fn unbind(&self) -> Result<(), Error> {
-        unsafe {
-            gl::BindVertexArray(0);
-            check_gl()?;
-        }
-        Ok(())
+    #[test]
+    #[should_panic(expected = "View overflows")]
+    fn view_mut_stride_overflow() {
+        let mut data = [0u8; 16];
+        CpuTextureViewMut::with_stride(&mut data, 1, (1, 3), usize::MAX / 2);
     }
-}
 
-impl Drop for VertexArray {
-    fn drop(&mut self) {
-        unsafe {
-            gl::DeleteVertexArrays(1, &self.id);
-            check_gl().expect("Failed to delete vertex array in drop impl");
-        }
+    #[test]
+    #[should_panic(expected = "less than width")]
+    fn view_stride_overlapping_rows() {
+        let data = [0u8; 16];
+        CpuTextureView::with_stride(&data, 0, (4, 2), 2);
     }
 }
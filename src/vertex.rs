@@ -0,0 +1,143 @@
+use crate::{
+    buffer::{Buffer, Pod},
+    vertex_array::VertexArray,
+    Error,
+};
+use gl::types::*;
+
+#[cfg(feature = "khygl-derive")]
+pub use khygl_derive::Vertex;
+
+// How the shader sees an attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttribFormat {
+    // float/vecN, integer values are converted as-is
+    Float,
+    // float/vecN, unsigned integers are mapped to 0..1 and signed ones to -1..1
+    Normalized,
+    // int/ivecN/uint/uvecN, only for integer types
+    Integer,
+}
+
+// One field of a Vertex: read from `layout(location = location) in` in the vertex shader
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttrib {
+    pub location: GLuint,
+    // 1 to 4
+    pub components: GLint,
+    // gl::FLOAT, gl::UNSIGNED_BYTE, etc.
+    pub type_: GLenum,
+    pub format: AttribFormat,
+    // byte offset of the field in the vertex
+    pub offset: GLuint,
+}
+
+// Rust types that can be the field of a Vertex
+pub trait AttribType {
+    const COMPONENTS: GLint;
+    const GL_TYPE: GLenum;
+    // whether AttribFormat::Integer is the default format
+    const INTEGER: bool;
+}
+
+macro_rules! attrib_type {
+    ($ty:ty, $gl_type:expr, $integer:expr) => {
+        impl AttribType for $ty {
+            const COMPONENTS: GLint = 1;
+            const GL_TYPE: GLenum = $gl_type;
+            const INTEGER: bool = $integer;
+        }
+    };
+}
+
+attrib_type!(f32, gl::FLOAT, false);
+attrib_type!(u8, gl::UNSIGNED_BYTE, true);
+attrib_type!(i8, gl::BYTE, true);
+attrib_type!(u16, gl::UNSIGNED_SHORT, true);
+attrib_type!(i16, gl::SHORT, true);
+attrib_type!(u32, gl::UNSIGNED_INT, true);
+attrib_type!(i32, gl::INT, true);
+
+macro_rules! attrib_array {
+    ($n:expr) => {
+        impl<T: AttribType> AttribType for [T; $n] {
+            const COMPONENTS: GLint = T::COMPONENTS * $n;
+            const GL_TYPE: GLenum = T::GL_TYPE;
+            const INTEGER: bool = T::INTEGER;
+        }
+    };
+}
+
+attrib_array!(2);
+attrib_array!(3);
+attrib_array!(4);
+
+// Describes the attributes of a vertex type, usually derived with khygl_derive::Vertex:
+//
+// #[derive(Clone, Copy, Vertex)]
+// #[repr(C)]
+// struct Point {
+//     position: [f32; 2],          // location 0
+//     #[vertex(normalized)]
+//     color: [u8; 4],              // location 1, vec4 in 0..1
+//     #[vertex(location = 3)]
+//     id: u32,                     // location 3, uint
+// }
+//
+// Locations default to the field index. The derive does not implement Pod, which needs an
+// unsafe impl that vouches for the struct having no padding.
+pub trait Vertex: Pod {
+    const ATTRIBS: &'static [VertexAttrib];
+}
+
+impl VertexArray {
+    // Vertex array reading V from buffer at bind index 0, with all attributes of V enabled
+    pub fn from_layout<V: Vertex>(buffer: &Buffer<V>) -> Result<Self, Error> {
+        let vertex_array = Self::new()?;
+        vertex_array.attach_layout(0, buffer, 0)?;
+        Ok(vertex_array)
+    }
+
+    // Binds buffer to bind_index and sets up the attributes of V to read from it, e.g. a
+    // second buffer of per-instance attributes with divisor 1. The locations of different
+    // layouts must not overlap.
+    pub fn attach_layout<V: Vertex>(
+        &self,
+        bind_index: GLuint,
+        buffer: &Buffer<V>,
+        divisor: GLuint,
+    ) -> Result<(), Error> {
+        self.bind_buffer_to_bind_index(bind_index, buffer, 0, std::mem::size_of::<V>() as GLsizei)?;
+        self.set_binding_divisor(bind_index, divisor)?;
+        for attrib in V::ATTRIBS {
+            self.enable_attrib(attrib.location)?;
+            self.associate_attrib_index_to_bind_index(attrib.location, bind_index)?;
+            match attrib.format {
+                AttribFormat::Float | AttribFormat::Normalized => self.attrib_format_float(
+                    attrib.location,
+                    attrib.components,
+                    attrib.type_,
+                    attrib.format == AttribFormat::Normalized,
+                    attrib.offset,
+                )?,
+                AttribFormat::Integer => {
+                    if attrib.type_ == gl::FLOAT {
+                        return Err(format!(
+                            "Attribute {} of {} is float, but has integer format",
+                            attrib.location,
+                            std::any::type_name::<V>()
+                        )
+                        .into());
+                    }
+                    self.attrib_format_int(
+                        attrib.location,
+                        attrib.components,
+                        attrib.type_,
+                        attrib.offset,
+                    )?
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+use crate::{
+    buffer::{Buffer, Pod},
+    check_gl, Error,
+};
+use gl::types::*;
+use std::ffi::c_void;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    Points,
+    Lines,
+    LineStrip,
+    LineLoop,
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+    LinesAdjacency,
+    LineStripAdjacency,
+    TrianglesAdjacency,
+    TriangleStripAdjacency,
+    // set the vertex count per patch with gl::PatchParameteri(gl::PATCH_VERTICES, n)
+    Patches,
+}
+
+impl Primitive {
+    pub fn gl_enum(self) -> GLenum {
+        match self {
+            Primitive::Points => gl::POINTS,
+            Primitive::Lines => gl::LINES,
+            Primitive::LineStrip => gl::LINE_STRIP,
+            Primitive::LineLoop => gl::LINE_LOOP,
+            Primitive::Triangles => gl::TRIANGLES,
+            Primitive::TriangleStrip => gl::TRIANGLE_STRIP,
+            Primitive::TriangleFan => gl::TRIANGLE_FAN,
+            Primitive::LinesAdjacency => gl::LINES_ADJACENCY,
+            Primitive::LineStripAdjacency => gl::LINE_STRIP_ADJACENCY,
+            Primitive::TrianglesAdjacency => gl::TRIANGLES_ADJACENCY,
+            Primitive::TriangleStripAdjacency => gl::TRIANGLE_STRIP_ADJACENCY,
+            Primitive::Patches => gl::PATCHES,
+        }
+    }
+}
+
+// Element types usable in an index buffer
+pub trait IndexType: Pod {
+    const GL_TYPE: GLenum;
+}
+
+impl IndexType for u8 {
+    const GL_TYPE: GLenum = gl::UNSIGNED_BYTE;
+}
+
+impl IndexType for u16 {
+    const GL_TYPE: GLenum = gl::UNSIGNED_SHORT;
+}
+
+impl IndexType for u32 {
+    const GL_TYPE: GLenum = gl::UNSIGNED_INT;
+}
+
+// Layout of the commands read by VertexArray::draw_arrays_indirect, as specified by GL
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawArraysIndirectCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first: u32,
+    pub base_instance: u32,
+}
+
+unsafe impl Pod for DrawArraysIndirectCommand {}
+
+// Layout of the commands read by VertexArray::draw_elements_indirect, as specified by GL
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawElementsIndirectCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub base_instance: u32,
+}
+
+unsafe impl Pod for DrawElementsIndirectCommand {}
+
+pub struct VertexArray {
+    pub id: GLuint,
+    // (gl type, size in bytes) of the indices in the element buffer
+    index_type: Option<(GLenum, usize)>,
+}
+
+// Steps:
+// 0) gl::BindAttribLocation on the shader to associate attrib_index to variable name
+// 1) bind VertexBuffer to a bind_index using bind_buffer_to_bind_index
+// 2) associate a attrib_index to a bind_index using associate_attrib_index_to_bind_index
+// 3) specify the format of an attrib_index using attrib_format_*
+// 4) optionally set_index_buffer, and set_binding_divisor for per-instance attributes
+// 5) use the shader and draw with draw_arrays/draw_elements and their variants
+// Steps 1 to 3 can be done from a vertex::Vertex description with from_layout/attach_layout.
+impl VertexArray {
+    pub fn new() -> Result<Self, Error> {
+        let mut id = 0;
+        unsafe {
+            gl::CreateVertexArrays(1, &mut id);
+            check_gl()?;
+        }
+        Ok(Self {
+            id,
+            index_type: None,
+        })
+    }
+
+    pub fn enable_attrib(&self, attrib_index: GLuint) -> Result<(), Error> {
+        unsafe {
+            gl::EnableVertexArrayAttrib(self.id, attrib_index);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    pub fn disable_attrib(&self, attrib_index: GLuint) -> Result<(), Error> {
+        unsafe {
+            gl::DisableVertexArrayAttrib(self.id, attrib_index);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    pub fn bind_buffer_to_bind_index<T: Pod>(
+        &self,
+        bind_index: GLuint,
+        buffer: &Buffer<T>,
+        offset: GLintptr,
+        stride: GLsizei,
+    ) -> Result<(), Error> {
+        unsafe {
+            gl::VertexArrayVertexBuffer(self.id, bind_index, buffer.id, offset, stride);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    pub fn associate_attrib_index_to_bind_index(
+        &self,
+        attrib_index: GLuint,
+        bind_index: GLuint,
+    ) -> Result<(), Error> {
+        unsafe {
+            gl::VertexArrayAttribBinding(self.id, attrib_index, bind_index);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    // size: num elements per vertex
+    // type: gl::FLOAT, etc.
+    // "relativeoffset is the offset, measured in basic machine units of the first element relative to the start of the vertex buffer binding this attribute fetches from."
+    pub fn attrib_format_float(
+        &self,
+        attrib_index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: bool,
+        relative_offset: GLuint,
+    ) -> Result<(), Error> {
+        unsafe {
+            let normalized = if normalized { gl::TRUE } else { gl::FALSE };
+            gl::VertexArrayAttribFormat(
+                self.id,
+                attrib_index,
+                size,
+                type_,
+                normalized,
+                relative_offset,
+            );
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    pub fn attrib_format_int(
+        &self,
+        attrib_index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        relative_offset: GLuint,
+    ) -> Result<(), Error> {
+        unsafe {
+            gl::VertexArrayAttribIFormat(self.id, attrib_index, size, type_, relative_offset);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    pub fn bind(&self) -> Result<(), Error> {
+        unsafe {
+            gl::BindVertexArray(self.id);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    pub fn unbind(&self) -> Result<(), Error> {
+        unsafe {
+            gl::BindVertexArray(0);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    // The element buffer is part of the vertex array state, the buffer has to outlive its use in
+    // draw_elements*
+    pub fn set_index_buffer<I: IndexType>(&mut self, buffer: &Buffer<I>) -> Result<(), Error> {
+        unsafe {
+            gl::VertexArrayElementBuffer(self.id, buffer.id);
+            check_gl()?;
+        }
+        self.index_type = Some((I::GL_TYPE, std::mem::size_of::<I>()));
+        Ok(())
+    }
+
+    pub fn clear_index_buffer(&mut self) -> Result<(), Error> {
+        unsafe {
+            gl::VertexArrayElementBuffer(self.id, 0);
+            check_gl()?;
+        }
+        self.index_type = None;
+        Ok(())
+    }
+
+    // Attributes fed from bind_index advance once every divisor instances instead of once per
+    // vertex, 0 restores per-vertex attributes. Divisors are set per bind index, so attributes
+    // sharing a bind index share the divisor.
+    pub fn set_binding_divisor(&self, bind_index: GLuint, divisor: GLuint) -> Result<(), Error> {
+        unsafe {
+            gl::VertexArrayBindingDivisor(self.id, bind_index, divisor);
+            check_gl()?;
+        }
+        Ok(())
+    }
+
+    fn index_type(&self) -> Result<(GLenum, usize), Error> {
+        self.index_type
+            .ok_or_else(|| "Vertex array has no index buffer, call set_index_buffer".into())
+    }
+
+    fn draw(&self, draw: impl FnOnce()) -> Result<(), Error> {
+        self.bind()?;
+        draw();
+        let result = check_gl();
+        self.unbind()?;
+        result
+    }
+
+    pub fn draw_arrays(
+        &self,
+        primitive: Primitive,
+        first: usize,
+        count: usize,
+    ) -> Result<(), Error> {
+        self.draw(|| unsafe {
+            gl::DrawArrays(primitive.gl_enum(), first as GLint, count as GLsizei);
+        })
+    }
+
+    pub fn draw_arrays_instanced(
+        &self,
+        primitive: Primitive,
+        first: usize,
+        count: usize,
+        instances: usize,
+        base_instance: usize,
+    ) -> Result<(), Error> {
+        self.draw(|| unsafe {
+            gl::DrawArraysInstancedBaseInstance(
+                primitive.gl_enum(),
+                first as GLint,
+                count as GLsizei,
+                instances as GLsizei,
+                base_instance as GLuint,
+            );
+        })
+    }
+
+    // first is in indices, base_vertex is added to every index read from the index buffer
+    pub fn draw_elements(
+        &self,
+        primitive: Primitive,
+        first: usize,
+        count: usize,
+        base_vertex: i32,
+    ) -> Result<(), Error> {
+        let (type_, index_size) = self.index_type()?;
+        self.draw(|| unsafe {
+            gl::DrawElementsBaseVertex(
+                primitive.gl_enum(),
+                count as GLsizei,
+                type_,
+                (first * index_size) as *const c_void,
+                base_vertex,
+            );
+        })
+    }
+
+    pub fn draw_elements_instanced(
+        &self,
+        primitive: Primitive,
+        first: usize,
+        count: usize,
+        base_vertex: i32,
+        instances: usize,
+        base_instance: usize,
+    ) -> Result<(), Error> {
+        let (type_, index_size) = self.index_type()?;
+        self.draw(|| unsafe {
+            gl::DrawElementsInstancedBaseVertexBaseInstance(
+                primitive.gl_enum(),
+                count as GLsizei,
+                type_,
+                (first * index_size) as *const c_void,
+                instances as GLsizei,
+                base_vertex,
+                base_instance as GLuint,
+            );
+        })
+    }
+
+    fn check_commands<C: Pod>(
+        commands: &Buffer<C>,
+        first: usize,
+        draw_count: usize,
+    ) -> Result<(), Error> {
+        if first + draw_count > commands.len() {
+            Err(format!(
+                "Indirect commands {}..{} out of range (length {})",
+                first,
+                first + draw_count,
+                commands.len()
+            )
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    // Issues draw_count draws with the commands at first..first + draw_count, which can be
+    // written by a compute shader
+    pub fn draw_arrays_indirect(
+        &self,
+        primitive: Primitive,
+        commands: &Buffer<DrawArraysIndirectCommand>,
+        first: usize,
+        draw_count: usize,
+    ) -> Result<(), Error> {
+        Self::check_commands(commands, first, draw_count)?;
+        self.draw(|| unsafe {
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, commands.id);
+            gl::MultiDrawArraysIndirect(
+                primitive.gl_enum(),
+                (first * std::mem::size_of::<DrawArraysIndirectCommand>()) as *const c_void,
+                draw_count as GLsizei,
+                0,
+            );
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        })
+    }
+
+    pub fn draw_elements_indirect(
+        &self,
+        primitive: Primitive,
+        commands: &Buffer<DrawElementsIndirectCommand>,
+        first: usize,
+        draw_count: usize,
+    ) -> Result<(), Error> {
+        Self::check_commands(commands, first, draw_count)?;
+        let (type_, _) = self.index_type()?;
+        self.draw(|| unsafe {
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, commands.id);
+            gl::MultiDrawElementsIndirect(
+                primitive.gl_enum(),
+                type_,
+                (first * std::mem::size_of::<DrawElementsIndirectCommand>()) as *const c_void,
+                draw_count as GLsizei,
+                0,
+            );
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        })
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.id);
+            check_gl().expect("Failed to delete vertex array in drop impl");
+        }
+    }
+}